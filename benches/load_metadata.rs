@@ -0,0 +1,54 @@
+//! Compare `NotesRepository::load` and `NotesRepository::load_metadata`
+//! on notes with a long text. Run with `cargo bench --bench load_metadata`
+
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use denote::{Id, NoteBuilder, NotesRepository};
+
+const NOTE_COUNT: u32 = 200;
+const LINES_PER_NOTE: usize = 5_000;
+
+fn time<F: FnMut()>(mut f: F) -> Duration {
+    let start = Instant::now();
+    f();
+    start.elapsed()
+}
+
+fn main() {
+    let temp_dir = tempfile::Builder::new()
+        .prefix("bench-denotes")
+        .tempdir()
+        .expect("should be able to create a temporary directory");
+    let notes = NotesRepository::open(&temp_dir).expect("temp dir should be a repository");
+    let text = "Some long line of text in the body of the note\n".repeat(LINES_PER_NOTE);
+    let mut relative_paths = vec![];
+    for i in 0..NOTE_COUNT {
+        let id = Id::from_str(&format!("20220707T14{:02}{:02}", i / 60, i % 60))
+            .expect("id should be valid");
+        let note = NoteBuilder::new()
+            .id(id)
+            .title(&format!("Note number {i}"))
+            .keyword("bench")
+            .text(&text)
+            .build()
+            .expect("note should be valid");
+        relative_paths.push(notes.save(&note).expect("note should be saved"));
+    }
+
+    let load = time(|| {
+        for relative_path in &relative_paths {
+            notes.load(relative_path).expect("note should load");
+        }
+    });
+    let load_metadata = time(|| {
+        for relative_path in &relative_paths {
+            notes
+                .load_metadata(relative_path)
+                .expect("metadata should load");
+        }
+    });
+
+    println!("load:          {load:?} for {NOTE_COUNT} notes");
+    println!("load_metadata: {load_metadata:?} for {NOTE_COUNT} notes");
+}
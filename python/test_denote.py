@@ -1,10 +1,15 @@
+import doctest
 import shelve
 import textwrap
 from datetime import datetime
 
 import pytest
 
+import denote
 from denote import (
+    DenoteError,
+    DenoteIOError,
+    DenoteParseError,
     FrontMatter,
     Id,
     Metadata,
@@ -20,7 +25,7 @@ def test_slugify():
 
 
 def test_invalid_id():
-    with pytest.raises(ValueError) as e:
+    with pytest.raises(DenoteParseError) as e:
         id = Id("bad")
 
 
@@ -37,6 +42,15 @@ def test_id_as_human_date():
     assert id.human_date() == "2022-07-07 14:27:08"
 
 
+def test_id_unix_secs_round_trip():
+    id = Id.from_unix_secs(1657204028)
+    assert str(id) == "20220707T142708"
+    assert id.to_unix_secs() == 1657204028
+
+    with pytest.raises(DenoteParseError):
+        Id.from_unix_secs(2**62)
+
+
 def test_can_build_id_from_date():
     now = datetime.now()
     id = Id.from_date(now)
@@ -53,6 +67,61 @@ def test_can_build_a_metadata_instance():
     assert metadata.relative_path == "2022/20220707T142708--this-is-a-title__k1_k2.md"
 
 
+def test_str():
+    id = Id("20220707T142708")
+    metadata = Metadata(id, "This is a title", ["k1", "k2"], "md")
+    note = Note(text="this is my note\n", metadata=metadata)
+
+    assert str(id) == "20220707T142708"
+    assert str(metadata) == "20220707T142708 -- This is a title [k1, k2]"
+    assert str(note) == "20220707T142708 -- This is a title [k1, k2]"
+
+
+def test_note_creation_date():
+    id = Id("20220707T142708")
+    metadata = Metadata(id, "This is a title", ["k1", "k2"], "md")
+    note = Note(text="this is my note\n", metadata=metadata)
+
+    created_at = note.created_at
+    assert (created_at.year, created_at.month, created_at.day) == (2022, 7, 7)
+    assert (created_at.hour, created_at.minute, created_at.second) == (14, 27, 8)
+
+
+def test_note_content_hash():
+    id = Id("20220707T142708")
+    metadata = Metadata(id, "This is a title", ["k1", "k2"], "md")
+    note = Note(text="this is my note\n", metadata=metadata)
+    other = Note(text="this is my other note\n", metadata=metadata)
+
+    assert len(note.content_hash) == 64
+    assert note.content_hash == Note(text="this is my note\n", metadata=metadata).content_hash
+    assert note.content_hash != other.content_hash
+
+
+def test_keywords_are_lowercased_unless_kept():
+    id = Id("20220707T142708")
+    metadata = Metadata(id, "t", ["RUST", "rust"], "md")
+    assert metadata.keywords == ["rust"]
+
+    metadata = Metadata(id, "t", ["RUST", "rust"], "md", normalize_keywords="keep")
+    assert metadata.keywords == ["RUST", "rust"]
+
+    with pytest.raises(DenoteParseError):
+        Metadata(id, "t", ["RUST"], "md", normalize_keywords="upper")
+
+
+def test_add_and_remove_keywords():
+    id = Id("20220707T142708")
+    metadata = Metadata(id, "This is a title", ["k2"], "md")
+
+    metadata.add_keyword("k1")
+    metadata.add_keyword("k2")
+    assert metadata.keywords == ["k1", "k2"]
+
+    metadata.remove_keyword("k2")
+    assert metadata.keywords == ["k1"]
+
+
 def test_can_parse_front_matter():
     text = textwrap.dedent(
         """\
@@ -68,6 +137,26 @@ def test_can_parse_front_matter():
     assert front_matter.keywords == ["k1", "k2"]
 
 
+def test_missing_title_is_none():
+    front_matter = FrontMatter.parse("title:\ndate: 2022-07-08 17:43:37\nkeywords: k1\n")
+    assert front_matter.title is None
+
+    front_matter = FrontMatter.parse("title: ''\ndate: 2022-07-08 17:43:37\nkeywords: k1\n")
+    assert front_matter.title == ""
+
+    metadata = Metadata(Id("20220707T142708"), None, ["k1"], "md")
+    assert metadata.title is None
+    assert metadata.relative_path == "2022/20220707T142708--untitled__k1.md"
+
+
+def test_create_front_matter():
+    front_matter = FrontMatter("Title", "2022-07-08 17:43:37", ["k1", "k2"])
+
+    assert front_matter.title == "Title"
+    assert front_matter.keywords == ["k1", "k2"]
+    assert FrontMatter.parse(front_matter.dump()) == front_matter
+
+
 def test_front_matter_roundtip():
     text = textwrap.dedent(
         """\
@@ -84,6 +173,14 @@ def test_front_matter_roundtip():
     assert loaded == original
 
 
+def test_word_count_and_reading_time():
+    metadata = Metadata(Id("20220707T142708"), "Words", [], "md")
+    note = Note(metadata, "one two\nthree\n")
+
+    assert note.word_count() == 3
+    assert note.reading_time_minutes(2) == 2
+
+
 def test_can_create_a_note():
     id = Id("20220707T142708")
     metadata = Metadata(id, "This is a title", ["k1", "k2"], "md")
@@ -102,6 +199,7 @@ def test_can_create_a_note():
         title: This is a title
         date: "2022-07-07 14:27:08"
         keywords: k1 k2
+        identifier: "20220707T142708"
         ---
         this is my note
         """
@@ -109,10 +207,23 @@ def test_can_create_a_note():
 
 
 def test_cannot_open_a_repository_from_a_file():
-    with pytest.raises(OSError):
+    with pytest.raises(DenoteIOError):
         NotesRepository.open(__file__)
 
 
+def test_denote_errors_share_a_base_class():
+    assert issubclass(DenoteParseError, DenoteError)
+    assert issubclass(DenoteIOError, OSError)
+    with pytest.raises(DenoteError):
+        FrontMatter.parse("not: [valid")
+
+
+def test_open_or_create_a_repository(tmp_path):
+    base_path = tmp_path / "notes"
+    NotesRepository.open_or_create(base_path)
+    assert base_path.is_dir()
+
+
 def test_markdown_import(tmp_path):
     notes_repository = NotesRepository.open(tmp_path)
     foo_md = tmp_path / "foo.md"
@@ -131,7 +242,9 @@ def test_markdown_import(tmp_path):
 
     new_text = (tmp_path / saved_path).read_text()
     actual_without_date = [
-        x for x in new_text.splitlines() if not x.startswith("date: ")
+        x
+        for x in new_text.splitlines()
+        if not x.startswith("date: ") and not x.startswith("identifier: ")
     ]
     expected_without_date = [
         x for x in contents.splitlines() if not x.startswith("date: ")
@@ -152,6 +265,17 @@ def test_loading_and_saving(tmp_path):
     notes_repository.load(relative_path)
 
 
+def test_save_full(tmp_path):
+    id = Id("20220707T142708")
+    metadata = Metadata(id, "This is a title", ["k1", "k2"], "md")
+    notes_repository = NotesRepository.open(tmp_path)
+
+    full_path = notes_repository.save_full(Note(text="this is my note\n", metadata=metadata))
+
+    assert full_path.is_absolute()
+    assert full_path == tmp_path / "2022" / "20220707T142708--this-is-a-title__k1_k2.md"
+
+
 def test_update_note_path_when_title_changes(tmp_path):
     id = Id("20220707T142708")
     metadata = Metadata(id, "old title", ["k1", "k2"], "md")
@@ -190,6 +314,86 @@ def test_update_note_path_when_keywords_change(tmp_path):
     assert "__tag1_tag2" in note.relative_path
 
 
+def test_load_metadata(tmp_path):
+    id = Id("20220707T142708")
+    metadata = Metadata(id, "This is a title", ["k1", "k2"], "md")
+    notes_repository = NotesRepository.open(tmp_path)
+    relative_path = notes_repository.save(Note(text="this is my note\n", metadata=metadata))
+
+    loaded = notes_repository.load_metadata(relative_path)
+
+    assert loaded.title == "This is a title"
+    assert loaded.keywords == ["k1", "k2"]
+
+
+def test_count(tmp_path):
+    notes_repository = NotesRepository.open(tmp_path)
+    assert notes_repository.count() == 0
+
+    id = Id("20220707T142708")
+    metadata = Metadata(id, "title", ["k1"], "md")
+    notes_repository.save(Note(text="this is my note\n", metadata=metadata))
+    (tmp_path / "2022" / "README.txt").write_text("not a note")
+
+    assert notes_repository.count() == 1
+
+
+def test_statistics(tmp_path):
+    id = Id("20220707T142708")
+    metadata = Metadata(id, "title", ["k1", "k2"], "md")
+    notes_repository = NotesRepository.open(tmp_path)
+    notes_repository.save(Note(text="this is my note\n", metadata=metadata))
+
+    stats = notes_repository.statistics()
+
+    assert stats.total_notes == 1
+    assert stats.total_words == 4
+    assert stats.years == [2022]
+    assert stats.unique_keywords == 2
+    assert stats.most_common_keyword == "k1"
+    assert stats.notes_per_year == {2022: 1}
+    assert stats.top_keywords == [("k1", 1), ("k2", 1)]
+    assert notes_repository.stats().total_words == 4
+    assert notes_repository.statistics(fast=True).total_words == 0
+
+
+def test_iterate_over_notes(tmp_path):
+    notes_repository = NotesRepository.open(tmp_path)
+    first = Note(
+        text="first note\n", metadata=Metadata(Id("20220707T142708"), "one", [], "md")
+    )
+    second = Note(
+        text="second note\n", metadata=Metadata(Id("20220708T152912"), "two", [], "md")
+    )
+    notes_repository.save(first)
+    notes_repository.save(second)
+    (tmp_path / "2022" / "20220709T101010--broken.md").write_text("no front matter")
+
+    with pytest.warns(UserWarning):
+        notes = list(notes_repository)
+
+    assert notes == [first, second]
+    assert len(notes_repository) == 3
+
+
+def test_module_doctests():
+    failures, _ = doctest.testmod(denote)
+    assert failures == 0
+
+
+def test_notes_by_keyword(tmp_path):
+    id = Id("20220707T142708")
+    metadata = Metadata(id, "title", ["k1", "k2"], "md")
+    note = Note(text="this is my note\n", metadata=metadata)
+    notes_repository = NotesRepository.open(tmp_path)
+    relative_path = notes_repository.save(note)
+
+    assert notes_repository.notes_by_keyword() == {
+        "k1": [str(relative_path)],
+        "k2": [str(relative_path)],
+    }
+
+
 class NoteShelf:
     def __init__(self, shelve_path):
         self.shelve_path = shelve_path
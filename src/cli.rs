@@ -1,16 +1,141 @@
 use crate::Result;
+use indicatif::ProgressBar;
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::str::FromStr;
+use std::time::Duration;
 use time::macros::format_description;
-use time::OffsetDateTime;
 
-use crate::{NotesRepository, OSError};
+use serde::Deserialize;
+
+use crate::{
+    is_year, parse_file_name, parse_file_name_with_config, parse_front_matter, sanitize_keyword,
+    Id, Io, IssueKind, Note, NotesRepository, OSError, ParseError, RepositoryConfig,
+    ValidationIssue, BACKUP_EXTENSION,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+/// How to print the results of commands like `validate`
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Default, Deserialize)]
+/// Settings read from `~/.config/denote/config.toml`
+/// All the fields are optional
+pub struct Config {
+    /// Used when `--base-path` is not given
+    pub base_path: Option<PathBuf>,
+    /// Used instead of $EDITOR
+    pub editor: Option<String>,
+    /// Pre-filled in the template of new notes
+    #[serde(default)]
+    pub keywords: Vec<String>,
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/denote`, or `~/.config/denote`
+    fn directory() -> Option<PathBuf> {
+        let config_home = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(config_home) => PathBuf::from(config_home),
+            None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(config_home.join("denote"))
+    }
+
+    /// Path of the config file - `$XDG_CONFIG_HOME/denote/config.toml`,
+    /// or `~/.config/denote/config.toml`
+    pub fn path() -> Option<PathBuf> {
+        Some(Self::directory()?.join("config.toml"))
+    }
+
+    /// Directory of the templates of new notes - `templates/` next to
+    /// the config file
+    pub fn templates_dir() -> Option<PathBuf> {
+        Some(Self::directory()?.join("templates"))
+    }
+
+    /// Read the config file, falling back to the default config when
+    /// there is no such file
+    pub fn load() -> Result<Self> {
+        match Self::path() {
+            Some(path) if path.exists() => Self::load_from(&path),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path).map_err(|source| Io {
+            message: format!("While reading {path:?}"),
+            source,
+        })?;
+        toml::from_str(&contents)
+            .map_err(|e| ParseError(format!("Invalid config in {path:?}: {e}")))
+    }
+}
+
+#[derive(Debug, Default)]
+/// Options for `new_note_with_options`
+pub struct NewNoteOptions {
+    /// Used instead of $EDITOR
+    pub editor: Option<String>,
+    /// Pre-filled in the template
+    pub title: Option<String>,
+    /// Use `<templates_dir>/<template_name>.md` instead of the built-in
+    /// template, when it exists
+    pub template_name: Option<String>,
+    pub templates_dir: Option<PathBuf>,
+    /// Pre-filled in the template, and added back to the note if they
+    /// were removed while editing
+    pub keywords: Vec<String>,
+    /// Do not save the note, only print where it would be saved
+    pub dry_run: bool,
+}
+
+impl From<&Config> for NewNoteOptions {
+    fn from(config: &Config) -> Self {
+        Self {
+            editor: config.editor.clone(),
+            title: None,
+            template_name: None,
+            templates_dir: Config::templates_dir(),
+            keywords: config.keywords.clone(),
+            dry_run: false,
+        }
+    }
+}
 
 /// Spawn $EDITOR in a tempory file, then save the
 /// note with the proper filename in `base_path`
 /// Return the path to the saved note
 pub fn new_note(base_path: &Path) -> Result<PathBuf> {
-    let now = OffsetDateTime::now_utc();
+    new_note_with_options(base_path, &NewNoteOptions::default())
+}
+
+/// Same as `new_note`, but with a custom editor, and a pre-filled title
+/// and keywords
+pub fn new_note_with_options(base_path: &Path, options: &NewNoteOptions) -> Result<PathBuf> {
+    create_note(base_path, options, true)
+}
+
+/// Same as `new_note_with_options`, but save the template as is instead
+/// of opening it in an editor - useful in scripts
+pub fn new_note_headless(base_path: &Path, options: &NewNoteOptions) -> Result<PathBuf> {
+    create_note(base_path, options, false)
+}
+
+fn create_note(base_path: &Path, options: &NewNoteOptions, edit: bool) -> Result<PathBuf> {
+    let notes = NotesRepository::open(base_path)?.with_dry_run(options.dry_run);
+    let keywords = options
+        .keywords
+        .iter()
+        .map(|k| sanitize_keyword(k))
+        .collect::<Result<Vec<_>>>()?;
+    let keywords = notes.config().normalize_keywords.apply(&keywords);
+    let now = notes.now();
     let format = format_description!("[year]-[month]-[day] [hour]:[minute]");
     let formatted_date = now
         .format(&format)
@@ -18,42 +143,625 @@ pub fn new_note(base_path: &Path) -> Result<PathBuf> {
 
     // Note: the date here is just for cosmetics - the 'real' date
     // will be set by the NotesRepository during import where there's
-    // an other call to OffsetDateTime::now()
-    let template = format!(
-        r#"---
+    // an other call to NotesRepository::now()
+    let template_keywords = keywords.join(" ");
+    // Note: a JSON string is also a valid YAML string, quoted if needed
+    let template_title = match &options.title {
+        Some(title) => serde_json::to_string(title).expect("strings are always serializable"),
+        None => String::new(),
+    };
+    let template = match read_template(options)? {
+        Some(template) => template
+            .replace("{{date}}", &formatted_date)
+            .replace("{{title}}", &template_title)
+            .replace("{{keywords}}", &template_keywords),
+        None => format!(
+            r#"---
 date: {formatted_date}
-title:
-keywords: 
+title: {template_title}
+keywords: {template_keywords}
 ---
     "#
-    );
+        ),
+    };
     let temp_dir = tempfile::Builder::new()
         .prefix("tmp-denotes")
         .tempdir()
-        .map_err(|e| OSError(format!("Could not create temporary directory: {e}")))?;
+        .map_err(|source| Io {
+            message: "Could not create temporary directory".to_string(),
+            source,
+        })?;
 
     let note_path = temp_dir.path().join("note.md");
-    std::fs::write(&note_path, template)
-        .map_err(|e| OSError(format!("Could not create makdown file: {e}")))?;
+    std::fs::write(&note_path, template).map_err(|source| Io {
+        message: "Could not create makdown file".to_string(),
+        source,
+    })?;
 
-    let editor =
-        std::env::var("EDITOR").map_err(|_| OSError("EDITOR should be set".to_string()))?;
+    if edit {
+        run_editor(options.editor.as_deref(), &note_path)?;
+    }
+    if !note_path.exists() {
+        return Err(OSError(
+            "editor exited successfuly but no file was written".to_string(),
+        ));
+    }
+
+    let imported_path = notes.import_from_markdown(&note_path)?;
+    if options.dry_run {
+        print_planned_actions(&notes);
+        return Ok(imported_path);
+    }
+    let mut note = notes.load(&imported_path)?;
+    if keywords.iter().all(|k| note.has_keyword(k)) {
+        return Ok(imported_path);
+    }
+    for keyword in &keywords {
+        note.add_keyword(keyword);
+    }
+    notes.write_and_rename(&imported_path, &note)?;
+    Ok(note.relative_path())
+}
+
+/// The contents of the template selected in `options`, if any.
+/// Templates can use the `{{date}}`, `{{title}}` and `{{keywords}}`
+/// placeholders
+fn read_template(options: &NewNoteOptions) -> Result<Option<String>> {
+    let (name, templates_dir) = match (&options.template_name, &options.templates_dir) {
+        (Some(name), Some(templates_dir)) => (name, templates_dir),
+        _ => return Ok(None),
+    };
+    let path = templates_dir.join(format!("{name}.md"));
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let contents = std::fs::read_to_string(&path).map_err(|source| Io {
+        message: format!("While reading template {path:?}"),
+        source,
+    })?;
+    Ok(Some(contents))
+}
+
+/// Spawn `editor` (or $EDITOR) on `path` and wait for it to exit
+fn run_editor(editor: Option<&str>, path: &Path) -> Result<()> {
+    let editor = match editor {
+        Some(editor) => editor.to_owned(),
+        None => std::env::var("EDITOR").map_err(|_| OSError("EDITOR should be set".to_string()))?,
+    };
 
     let status = Command::new(&editor)
-        .args([&note_path.as_os_str()])
+        .args([path.as_os_str()])
         .status()
-        .map_err(|e| OSError(format!("Could not spawn {editor}: {e}")))?;
+        .map_err(|source| Io {
+            message: format!("Could not spawn {editor}"),
+            source,
+        })?;
 
     if !status.success() {
-        return Err(OSError("editor did not exit sucessfully".to_string()))?;
+        return Err(OSError("editor did not exit sucessfully".to_string()));
     }
-    if !&note_path.exists() {
-        return Err(OSError(
-            "editor exited successfuly but no file was written".to_string(),
-        ))?;
+    Ok(())
+}
+
+/// Relative paths of the notes whose id starts with `query`,
+/// or whose slug is exactly `query`
+fn find_notes(notes: &NotesRepository, query: &str) -> Result<Vec<PathBuf>> {
+    let mut res = vec![];
+    for relative_path in notes.note_paths()? {
+        let info = parse_file_name_with_config(&relative_path, notes.config())?;
+        if info.id().as_str().starts_with(query) || info.slug() == query {
+            res.push(relative_path);
+        }
+    }
+    Ok(res)
+}
+
+/// Open the note matching `id_or_slug` in `editor` - or $EDITOR - then
+/// save it, renaming it if required
+/// The query must match exactly one note, either by id prefix or by slug
+/// With `dry_run`, the changes are not saved
+pub fn edit(base_path: &Path, id_or_slug: &str, editor: Option<&str>, dry_run: bool) -> Result<()> {
+    let notes = NotesRepository::open(base_path)?.with_dry_run(dry_run);
+    let relative_path = find_note(&notes, id_or_slug)?;
+    let note = notes.load(&relative_path)?;
+    let note = edit_in_editor(note, editor)?;
+    let new_path = notes.write_and_rename(&relative_path, &note)?;
+    print_planned_actions(&notes);
+    println!("{}", new_path.display());
+    Ok(())
+}
+
+/// Print what `notes` would have changed on disk, if it is in dry-run mode
+pub fn print_planned_actions(notes: &NotesRepository) {
+    for action in notes.take_planned_actions() {
+        println!("[dry-run] {action}");
+    }
+}
+
+/// Warn about the notes whose slug `NotesRepository::update` replaced
+pub fn print_slug_mismatches(notes: &NotesRepository) {
+    for mismatch in notes.take_slug_mismatches() {
+        eprintln!("{mismatch}");
+    }
+}
+
+/// The relative path of the only note matching `id_or_slug` - see `find_notes`
+fn find_note(notes: &NotesRepository, id_or_slug: &str) -> Result<PathBuf> {
+    let mut matches = find_notes(notes, id_or_slug)?;
+    match matches.len() {
+        1 => Ok(matches.remove(0)),
+        0 => Err(OSError(format!("No note matching {id_or_slug}"))),
+        _ => {
+            let paths: Vec<_> = matches.iter().map(|p| p.display().to_string()).collect();
+            Err(OSError(format!(
+                "Several notes matching {id_or_slug}: {}",
+                paths.join(", ")
+            )))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// What `show` prints
+pub enum ShowMode {
+    /// The front matter and the text, as in the file
+    Full,
+    /// The text only
+    BodyOnly,
+    /// The metadata, as JSON
+    Metadata,
+}
+
+/// Print the note matching `id_or_slug` to stdout, so that it can be
+/// piped to other tools
+/// The query must match exactly one note, either by id prefix or by slug
+pub fn show(base_path: &Path, id_or_slug: &str, mode: ShowMode) -> Result<()> {
+    print!("{}", render_note(base_path, id_or_slug, mode)?);
+    Ok(())
+}
+
+pub(crate) fn render_note(base_path: &Path, id_or_slug: &str, mode: ShowMode) -> Result<String> {
+    let notes = NotesRepository::open(base_path)?;
+    let relative_path = find_note(&notes, id_or_slug)?;
+    let note = notes.load(&relative_path)?;
+    Ok(match mode {
+        ShowMode::Full => note.dump(),
+        ShowMode::BodyOnly => note.body_text().to_string(),
+        ShowMode::Metadata => {
+            let json = serde_json::to_string_pretty(note.metadata())
+                .expect("metadata should always be serializable");
+            format!("{json}\n")
+        }
+    })
+}
+
+/// Create a copy of the note with the given id, with a new id, open it
+/// in `editor` - or $EDITOR - then save it
+/// Return the path to the saved note
+pub fn clone_note(
+    base_path: &Path,
+    id: &str,
+    editor: Option<&str>,
+    dry_run: bool,
+) -> Result<PathBuf> {
+    let notes = NotesRepository::open(base_path)?.with_dry_run(dry_run);
+    let id = Id::from_str(id)?;
+    let relative_path = notes
+        .find_path_by_id(&id)?
+        .ok_or_else(|| OSError(format!("No note found with id {}", id.as_str())))?;
+    let note = notes.load(&relative_path)?;
+    // Note: several clones in the same second would get the same id otherwise
+    let new_id = notes.next_free_id(notes.now())?;
+    let clone = edit_in_editor(note.clone_with_new_id(new_id), editor)?;
+    let new_path = notes.save_full(&clone)?;
+    print_planned_actions(&notes);
+    println!("{}", new_path.display());
+    Ok(new_path)
+}
+
+/// Duplicate the note with the given id in the same repository, under
+/// a new id, and print the path of the copy. The original note is kept
+pub fn copy_note(base_path: &Path, id: &str, dry_run: bool) -> Result<PathBuf> {
+    let notes = NotesRepository::open(base_path)?.with_dry_run(dry_run);
+    let id = Id::from_str(id)?;
+    let new_path = notes.duplicate(&id)?;
+    print_planned_actions(&notes);
+    println!("{}", base_path.join(&new_path).display());
+    Ok(new_path)
+}
+
+/// Open the note in `editor` - or $EDITOR - and return it updated with
+/// the edited front matter and text. The id is kept
+fn edit_in_editor(note: Note, editor: Option<&str>) -> Result<Note> {
+    let temp_dir = tempfile::Builder::new()
+        .prefix("tmp-denotes")
+        .tempdir()
+        .map_err(|source| Io {
+            message: "Could not create temporary directory".to_string(),
+            source,
+        })?;
+    let note_path = temp_dir.path().join("note.md");
+    std::fs::write(&note_path, note.dump()).map_err(|source| Io {
+        message: "Could not create makdown file".to_string(),
+        source,
+    })?;
+
+    run_editor(editor, &note_path)?;
+
+    let contents = std::fs::read_to_string(&note_path).map_err(|source| Io {
+        message: format!("While reading {note_path:?}"),
+        source,
+    })?;
+    let (front_matter, text) = parse_front_matter(&contents)?;
+    let mut note = Note::new(note.metadata, text.to_string());
+    note.update(&front_matter);
+    Ok(note)
+}
+
+/// Promote the draft with the given id to a regular note, renaming it
+/// Return the new path of the note
+pub fn promote_draft(base_path: &Path, id: &str, dry_run: bool) -> Result<PathBuf> {
+    let notes = NotesRepository::open(base_path)?.with_dry_run(dry_run);
+    let id = Id::from_str(id)?;
+    let relative_path = notes
+        .find_path_by_id(&id)?
+        .ok_or_else(|| OSError(format!("No note found with id {}", id.as_str())))?;
+    let mut note = notes.load(&relative_path)?;
+    if !note.is_draft() {
+        return Err(OSError(format!(
+            "{} is not a draft",
+            relative_path.display()
+        )));
+    }
+    note.promote();
+    let new_path = notes.write_and_rename(&relative_path, &note)?;
+    print_planned_actions(&notes);
+    Ok(new_path)
+}
+
+/// Print the paths of the notes linking to the note with the given id
+pub fn backlinks(base_path: &Path, id: &str) -> Result<()> {
+    let notes = NotesRepository::open(base_path)?;
+    let id = Id::from_str(id)?;
+    for relative_path in notes.backlinks_for(&id)? {
+        println!("{}", base_path.join(relative_path).display());
+    }
+    Ok(())
+}
+
+/// Print a summary of the notes in `base_path`
+/// With `fast`, skip counting words
+pub fn stats(base_path: &Path, fast: bool) -> Result<()> {
+    let notes = NotesRepository::open(base_path)?;
+    let stats = notes.statistics(fast)?;
+    let years: Vec<_> = stats.years.iter().map(|y| y.to_string()).collect();
+    println!("Notes: {}", stats.total_notes);
+    if !fast {
+        println!("Words: {}", stats.total_words);
     }
+    println!("Bytes: {}", stats.total_bytes);
+    println!("Years: {}", years.join(" "));
+    for (year, count) in &stats.notes_per_year {
+        println!("  {year}: {count}");
+    }
+    println!("Keywords: {}", stats.unique_keywords);
+    for (keyword, count) in stats.top_keywords.iter().take(10) {
+        println!("  {keyword}: {count}");
+    }
+    Ok(())
+}
+
+/// Print all the notes to stdout. With `stream`, JSON is printed
+/// one note per line as the notes are loaded, instead of as a single array.
+/// The text format concatenates the notes as markdown, and does not stream
+pub fn export(base_path: &Path, format: OutputFormat, stream: bool) -> Result<()> {
+    let notes = NotesRepository::open(base_path)?;
+    match format {
+        OutputFormat::Json if stream => {
+            let stdout = std::io::stdout();
+            let mut lock = stdout.lock();
+            notes.export_ndjson(&mut lock)?;
+        }
+        OutputFormat::Json => println!("{}", notes.export_to_json()?),
+        OutputFormat::Text => {
+            let ids = notes
+                .note_paths()?
+                .iter()
+                .map(|p| Ok(parse_file_name_with_config(p, notes.config())?.id().clone()))
+                .collect::<Result<Vec<_>>>()?;
+            print!("{}", notes.export_combined(&ids)?);
+        }
+    }
+    Ok(())
+}
+
+/// Print the paths of the notes created on `date`, written as `YYYY-MM-DD`
+pub fn daily(base_path: &Path, date: &str) -> Result<()> {
+    let format = format_description!("[year]-[month]-[day]");
+    let date = time::Date::parse(date, &format)
+        .map_err(|e| ParseError(format!("Could not parse date '{date}': {e}")))?;
+    let notes = NotesRepository::open(base_path)?;
+    let year = u16::try_from(date.year())
+        .map_err(|_| ParseError(format!("invalid year: {}", date.year())))?;
+    for note in notes.notes_created_on(year, date.month().into(), date.day())? {
+        println!("{}", base_path.join(note.relative_path()).display());
+    }
+    Ok(())
+}
 
+/// How long to wait for the writes to settle before updating notes -
+/// editors often write the same file several times in a row
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Relative path of the note to update when `full_path` changes, or None
+/// if `full_path` is not a note - index, temporary files, and so on
+pub(crate) fn changed_note(base_path: &Path, full_path: &Path) -> Option<PathBuf> {
+    if !full_path.is_file() {
+        return None;
+    }
+    let relative_path = full_path.strip_prefix(base_path).ok()?;
+    // Note: notes always live in `<year>/<file>` - this skips the index
+    // and any other file at the top level
+    let year = relative_path.parent()?.to_str()?;
+    if !is_year(year) || relative_path.extension()? == BACKUP_EXTENSION {
+        return None;
+    }
+    parse_file_name(relative_path).ok()?;
+    Some(relative_path.to_path_buf())
+}
+
+/// Watch the notes in `base_path` and rename them as soon as
+/// their title or keywords change. Never returns, unless the
+/// watcher fails
+pub fn watch(base_path: &Path) -> Result<()> {
+    let base_path = base_path.canonicalize().map_err(|source| Io {
+        message: format!("While resolving {base_path:?}"),
+        source,
+    })?;
     let notes = NotesRepository::open(&base_path)?;
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)
+        .map_err(|e| OSError(format!("Could not create watcher: {e}")))?;
+    watcher
+        .watch(&base_path, RecursiveMode::Recursive)
+        .map_err(|e| OSError(format!("Could not watch {base_path:?}: {e}")))?;
+    println!("Watching {}", base_path.display());
+
+    let mut changed_paths = BTreeSet::new();
+    loop {
+        let event = receiver
+            .recv()
+            .map_err(|_| OSError("File watcher stopped".to_string()))?;
+        collect_changed_paths(event, &mut changed_paths);
+        while let Ok(event) = receiver.recv_timeout(WATCH_DEBOUNCE) {
+            collect_changed_paths(event, &mut changed_paths);
+        }
+        for full_path in std::mem::take(&mut changed_paths) {
+            let relative_path = match changed_note(&base_path, &full_path) {
+                Some(relative_path) => relative_path,
+                None => continue,
+            };
+            // Note: `update` prints the renames it performs
+            if let Err(e) = notes.update(&relative_path) {
+                eprintln!("Could not update {}: {e}", relative_path.display());
+            }
+            print_slug_mismatches(&notes);
+        }
+    }
+}
 
-    notes.import_from_markdown(&note_path)
+fn collect_changed_paths(event: notify::Result<notify::Event>, paths: &mut BTreeSet<PathBuf>) {
+    match event {
+        Ok(event) => {
+            if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                paths.extend(event.paths);
+            }
+        }
+        Err(e) => eprintln!("Watch error: {e}"),
+    }
+}
+
+fn suggested_fix(issue: &ValidationIssue) -> String {
+    match (&issue.kind, &issue.suggested_new_path) {
+        (_, Some(new_path)) => format!("rename to {}", new_path.display()),
+        (IssueKind::InvalidNote, None) => "fix the front matter".to_string(),
+        (IssueKind::WrongPath | IssueKind::MisfiledYear, None) => "rename the note".to_string(),
+        (IssueKind::IdMismatch, None) => "fix the identifier".to_string(),
+        (IssueKind::DuplicateId, None) => "change the id".to_string(),
+        (IssueKind::DuplicateSlug, None) => "change the title".to_string(),
+        (IssueKind::EmptyKeywords, None) => "add a keyword".to_string(),
+        (IssueKind::MissingDraftKeyword, None) => "add the draft keyword".to_string(),
+    }
+}
+
+fn print_issues(issues: &[ValidationIssue]) {
+    let headers = ["PATH", "KIND", "SUGGESTED FIX"];
+    let rows: Vec<[String; 3]> = issues
+        .iter()
+        .map(|w| {
+            [
+                w.path.display().to_string(),
+                w.kind.to_string(),
+                suggested_fix(w),
+            ]
+        })
+        .collect();
+    let path_width = rows
+        .iter()
+        .map(|r| r[0].len())
+        .fold(headers[0].len(), usize::max);
+    let kind_width = rows
+        .iter()
+        .map(|r| r[1].len())
+        .fold(headers[1].len(), usize::max);
+    println!(
+        "{:path_width$}  {:kind_width$}  {}",
+        headers[0], headers[1], headers[2]
+    );
+    for [path, kind, fix] in &rows {
+        println!("{path:path_width$}  {kind:kind_width$}  {fix}");
+    }
+}
+
+/// Check the notes in `base_path` and print the issues found, with
+/// a suggested fix for each of them.
+/// With `fix`, also rename the notes that have a suggested path
+/// Return true if there was no issue
+/// With `dry_run`, only print the renames `fix` would perform
+pub fn validate(base_path: &Path, fix: bool, format: OutputFormat, dry_run: bool) -> Result<bool> {
+    let notes = NotesRepository::open(base_path)?.with_dry_run(dry_run);
+    let issues = notes.validate()?;
+    match format {
+        OutputFormat::Text => {
+            if issues.is_empty() {
+                println!("No issues");
+            } else {
+                print_issues(&issues);
+            }
+        }
+        OutputFormat::Json => {
+            let json = serde_json::to_string_pretty(&issues)
+                .expect("issues should always be serializable");
+            println!("{json}");
+        }
+    }
+
+    if fix {
+        let renames = notes.repair_filenames()?;
+        if format == OutputFormat::Text {
+            if dry_run {
+                print_planned_actions(&notes);
+            } else {
+                for (old, new) in renames {
+                    println!("{} -> {}", old.display(), new.display());
+                }
+            }
+        }
+    }
+
+    Ok(issues.is_empty())
+}
+
+/// Rename the notes in `base_path` whose path does not match their
+/// metadata.
+/// With `dry_run`, only print the proposed renames as a diff.
+/// With `backup_dir`, copy all the notes there before renaming anything
+/// Return true if there was nothing to rename
+pub fn repair(base_path: &Path, dry_run: bool, backup_dir: Option<&Path>) -> Result<bool> {
+    let notes = NotesRepository::open(base_path)?;
+    if dry_run {
+        let issues: Vec<_> = notes
+            .validate()?
+            .into_iter()
+            .filter(|i| i.suggested_new_path.is_some())
+            .collect();
+        for issue in &issues {
+            let new_path = issue
+                .suggested_new_path
+                .as_ref()
+                .expect("issues were filtered");
+            println!("- {}", issue.path.display());
+            println!("+ {}", new_path.display());
+        }
+        return Ok(issues.is_empty());
+    }
+
+    if let Some(backup_dir) = backup_dir {
+        let copied = notes.backup_to(backup_dir)?;
+        println!("Copied {copied} notes to {}", backup_dir.display());
+    }
+    let progress_bar = ProgressBar::new(0);
+    let callback_bar = progress_bar.clone();
+    let notes = notes.with_progress(Box::new(move |current, total| {
+        callback_bar.set_length(total as u64);
+        callback_bar.set_position(current as u64);
+    }));
+    let renames = notes.repair_filenames()?;
+    progress_bar.finish_and_clear();
+    for (old, new) in &renames {
+        println!("{} -> {}", old.display(), new.display());
+    }
+    Ok(renames.is_empty())
+}
+
+fn relative_path(base_path: &Path, full_path: &Path) -> Result<PathBuf> {
+    pathdiff::diff_paths(full_path, base_path).ok_or_else(|| {
+        OSError(format!(
+            "{full_path:?} and {base_path:?} should be relative to each other"
+        ))
+    })
+}
+
+/// Apply `edit` to the note at `full_path`, save it,
+/// then rename it if required
+/// Return the new path of the note
+fn edit_note(
+    base_path: &Path,
+    full_path: &Path,
+    dry_run: bool,
+    edit: impl FnOnce(&RepositoryConfig, &mut Note) -> Result<()>,
+) -> Result<PathBuf> {
+    let notes = NotesRepository::open(base_path)?.with_dry_run(dry_run);
+    let relative_path = relative_path(base_path, full_path)?;
+    let mut note = notes.load(&relative_path)?;
+    edit(notes.config(), &mut note)?;
+    let new_path = notes.write_and_rename(&relative_path, &note)?;
+    print_planned_actions(&notes);
+    Ok(new_path)
+}
+
+/// Add a keyword to the note at `full_path`, sanitized and normalized
+/// as the repository says
+/// Return the new path of the note
+pub fn add_tag(
+    base_path: &Path,
+    full_path: &Path,
+    keyword: &str,
+    dry_run: bool,
+) -> Result<PathBuf> {
+    edit_note(base_path, full_path, dry_run, |config, note| {
+        note.add_keyword(&config.sanitize_keyword(keyword)?);
+        Ok(())
+    })
+}
+
+/// Remove a keyword from the note at `full_path`
+/// Return the new path of the note
+pub fn remove_tag(
+    base_path: &Path,
+    full_path: &Path,
+    keyword: &str,
+    dry_run: bool,
+) -> Result<PathBuf> {
+    edit_note(base_path, full_path, dry_run, |config, note| {
+        note.remove_keyword(&config.normalize_keyword(keyword));
+        Ok(())
+    })
+}
+
+/// Replace the keyword `old` by `new` in all the notes of `base_path`
+/// With `backup_dir`, copy all the notes there before renaming anything
+pub fn rename_tag(
+    base_path: &Path,
+    old: &str,
+    new: &str,
+    dry_run: bool,
+    backup_dir: Option<&Path>,
+) -> Result<()> {
+    let notes = NotesRepository::open(base_path)?.with_dry_run(dry_run);
+    if let Some(backup_dir) = backup_dir {
+        let copied = notes.backup_to(backup_dir)?;
+        if !dry_run {
+            println!("Copied {copied} notes to {}", backup_dir.display());
+        }
+    }
+    let new_paths = notes.rename_keyword_globally(old, new)?;
+    if dry_run {
+        print_planned_actions(&notes);
+        return Ok(());
+    }
+    for relative_path in new_paths {
+        println!("{}", relative_path.display());
+    }
+    Ok(())
 }
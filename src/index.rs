@@ -0,0 +1,164 @@
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use rusqlite::{params, Connection, Params};
+
+use crate::{parse_file_name_with_config, Id, Io, OSError, RepositoryConfig, Result};
+
+/// Name of the SQLite database, stored directly in the base path
+/// of the repository
+pub(crate) const INDEX_FILE_NAME: &str = "denote.db";
+
+fn to_error(e: rusqlite::Error) -> crate::Error {
+    OSError(format!("index error: {e}"))
+}
+
+fn insert(
+    connection: &Connection,
+    base_path: &Path,
+    relative_path: &Path,
+    config: &RepositoryConfig,
+) -> Result<()> {
+    let full_path = base_path.join(relative_path);
+    let info = parse_file_name_with_config(relative_path, config)?;
+    let mtime = std::fs::metadata(&full_path)
+        .and_then(|m| m.modified())
+        .map_err(|source| Io {
+            message: format!("While reading mtime of {full_path:?}"),
+            source,
+        })?;
+    let mtime = mtime
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default();
+    connection
+        .execute(
+            "INSERT OR REPLACE INTO notes (id, slug, keywords, extension, relative_path, mtime)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                info.id().as_str(),
+                info.slug(),
+                info.keywords().join(" "),
+                info.extension(),
+                relative_path.to_string_lossy().to_string(),
+                mtime
+            ],
+        )
+        .map_err(to_error)?;
+    Ok(())
+}
+
+#[derive(Debug)]
+/// Contain the filename data of every note in the repository,
+/// so that queries do not have to walk the `<year>` directories
+pub(crate) struct Index {
+    connection: Connection,
+}
+
+impl Index {
+    /// Open the index stored in `base_path`, creating the database
+    /// and the `notes` table when needed
+    pub(crate) fn open(base_path: &Path) -> Result<Self> {
+        let connection = Connection::open(base_path.join(INDEX_FILE_NAME)).map_err(to_error)?;
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS notes (
+                    id TEXT NOT NULL,
+                    slug TEXT NOT NULL,
+                    keywords TEXT NOT NULL,
+                    extension TEXT NOT NULL,
+                    relative_path TEXT PRIMARY KEY,
+                    mtime INTEGER NOT NULL
+                )",
+            )
+            .map_err(to_error)?;
+        Ok(Self { connection })
+    }
+
+    /// Replace the whole contents of the index. The keywords are
+    /// normalized as `config` says
+    pub(crate) fn rebuild(
+        &self,
+        base_path: &Path,
+        relative_paths: &[PathBuf],
+        config: &RepositoryConfig,
+    ) -> Result<()> {
+        let transaction = self.connection.unchecked_transaction().map_err(to_error)?;
+        transaction
+            .execute("DELETE FROM notes", [])
+            .map_err(to_error)?;
+        for relative_path in relative_paths {
+            insert(&transaction, base_path, relative_path, config)?;
+        }
+        transaction.commit().map_err(to_error)
+    }
+
+    /// Record that the note at `old` (if any) now lives at `new`
+    pub(crate) fn replace(
+        &self,
+        base_path: &Path,
+        old: Option<&Path>,
+        new: &Path,
+        config: &RepositoryConfig,
+    ) -> Result<()> {
+        let transaction = self.connection.unchecked_transaction().map_err(to_error)?;
+        if let Some(old) = old {
+            transaction
+                .execute(
+                    "DELETE FROM notes WHERE relative_path = ?1",
+                    params![old.to_string_lossy().to_string()],
+                )
+                .map_err(to_error)?;
+        }
+        insert(&transaction, base_path, new, config)?;
+        transaction.commit().map_err(to_error)
+    }
+
+    pub(crate) fn remove(&self, relative_path: &Path) -> Result<()> {
+        self.connection
+            .execute(
+                "DELETE FROM notes WHERE relative_path = ?1",
+                params![relative_path.to_string_lossy().to_string()],
+            )
+            .map_err(to_error)?;
+        Ok(())
+    }
+
+    pub(crate) fn count(&self) -> Result<usize> {
+        let count: i64 = self
+            .connection
+            .query_row("SELECT COUNT(*) FROM notes", [], |row| row.get(0))
+            .map_err(to_error)?;
+        Ok(count as usize)
+    }
+
+    pub(crate) fn find_by_keyword(&self, keyword: &str) -> Result<Vec<PathBuf>> {
+        self.query_paths(
+            "SELECT relative_path FROM notes
+             WHERE instr(' ' || keywords || ' ', ' ' || ?1 || ' ') > 0
+             ORDER BY relative_path",
+            params![keyword],
+        )
+    }
+
+    pub(crate) fn filter_by_date_range(&self, start: &Id, end: &Id) -> Result<Vec<PathBuf>> {
+        self.query_paths(
+            "SELECT relative_path FROM notes
+             WHERE id >= ?1 AND id <= ?2
+             ORDER BY id",
+            params![start.as_str(), end.as_str()],
+        )
+    }
+
+    fn query_paths(&self, sql: &str, params: impl Params) -> Result<Vec<PathBuf>> {
+        let mut statement = self.connection.prepare(sql).map_err(to_error)?;
+        let rows = statement
+            .query_map(params, |row| row.get::<_, String>(0))
+            .map_err(to_error)?;
+        let mut res = vec![];
+        for row in rows {
+            res.push(PathBuf::from(row.map_err(to_error)?));
+        }
+        Ok(res)
+    }
+}
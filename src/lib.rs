@@ -1,14 +1,42 @@
+//! Implementation of [denote](https://protesilaos.com/emacs/denote) in Rust
+//!
+//! Notes are stored in `<year>` directories, in files named like
+//! `20220707T142708--this-is-a-title__k1_k2.md`, and start with a YAML
+//! front matter:
+//!
+//! ```
+//! use denote::parse_front_matter;
+//!
+//! let contents = "---\ntitle: This is a title\ndate: 2022-07-07 14:27:08\nkeywords: k1 k2\n---\nThis is my note\n";
+//! let (front_matter, text) = parse_front_matter(contents).unwrap();
+//! assert_eq!(front_matter.title(), Some("This is a title"));
+//! assert_eq!(text, "This is my note\n");
+//! ```
+//!
+//! Use `NotesRepository` to load, save, and rename notes in a directory.
+//!
+//! The `draft` keyword is reserved: notes having it are drafts (see
+//! `Note::is_draft`), and are expected to be skipped when publishing.
+//! `Note::promote` turns a draft into a regular note.
+
 use std::{
-    path::{Path, PathBuf},
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    io::{BufRead, BufReader, Write},
+    path::{Component, Path, PathBuf},
     str::FromStr,
+    sync::Arc,
+    time::SystemTime,
 };
 
 use lazy_static::lazy_static;
 use regex::{Regex, RegexBuilder};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use thiserror::Error;
+use time::format_description::well_known::Rfc3339;
 use time::macros::format_description;
-use time::OffsetDateTime;
+use time::{Date, OffsetDateTime, PrimitiveDateTime, UtcOffset};
 
 /// Tools for command-line usage
 pub mod cli;
@@ -16,6 +44,11 @@ pub mod cli;
 /// Python bindings
 mod python;
 
+/// SQLite index of the notes
+mod index;
+
+use index::Index;
+
 lazy_static! {
     static ref FILENAME_RE: Regex = RegexBuilder::new(
         r"
@@ -25,26 +58,60 @@ lazy_static! {
           __
           (.*)
           \.
-          ([a-z]+)
+          ([a-zA-Z]+)
         "
     )
     .ignore_whitespace(true)
     .build()
     .expect("syntax error in static regex");
+    /// `[[target]]` or `[[target|text]]`
+    static ref WIKILINK_RE: Regex =
+        Regex::new(r"\[\[([^\]|]+)(?:\|[^\]]*)?\]\]").expect("syntax error in static regex");
+    /// `[text](url)` or `[text](url "title")`
+    static ref MARKDOWN_LINK_RE: Regex =
+        Regex::new(r#"\[([^\[\]]*)\]\(([^)\s]+)(?:\s+"[^"]*")?\)"#)
+            .expect("syntax error in static regex");
+    /// `<https://example.com>`
+    static ref AUTOLINK_RE: Regex =
+        Regex::new(r"<([a-zA-Z][a-zA-Z0-9+.-]*://[^>\s]+)>").expect("syntax error in static regex");
 }
 
 #[derive(Debug, Error)]
 /// Variant of the errors returned by the libray
+/// `Yaml` and `Io` keep the underlying error, so that it
+/// can be retrieved with `std::error::Error::source()`
 #[non_exhaustive]
 pub enum Error {
-    #[error("parse error")]
+    #[error("parse error: {0}")]
     ParseError(String),
-    #[error("os error")]
+    #[error("os error: {0}")]
     OSError(String),
+    #[error("{message}: {source}")]
+    Yaml {
+        message: String,
+        #[source]
+        source: serde_yaml::Error,
+    },
+    #[error("{message}: {source}")]
+    Io {
+        message: String,
+        #[source]
+        source: std::io::Error,
+    },
 }
 
 use Error::*;
 
+/// Use `map_err` instead to give some context about the failed operation
+impl From<std::io::Error> for Error {
+    fn from(source: std::io::Error) -> Self {
+        Io {
+            message: "I/O error".to_string(),
+            source,
+        }
+    }
+}
+
 /// Result type for this library
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -61,16 +128,253 @@ fn name_from_relative_path(relative_path: &Path) -> String {
     last.to_string_lossy().into_owned()
 }
 
+/// Score how well `text` matches `query`, ignoring case, or None if the
+/// characters of `query` do not all appear in `text`, in order.
+/// Each matched character is worth 1 point, plus 2 when it follows the
+/// previous match directly, plus 2 when it starts a word - so `mt`
+/// matches "My title" better than "Mattress"
+fn fuzzy_score(query: &str, text: &str) -> Option<u32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous_match = None;
+    for c in query.to_lowercase().chars() {
+        let found = position + text[position..].iter().position(|&t| t == c)?;
+        score += 1;
+        if previous_match.map(|p| p + 1) == Some(found) {
+            score += 2;
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 2;
+        }
+        previous_match = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
+
+fn deduplicate(keywords: &[String]) -> Vec<String> {
+    let mut res: Vec<String> = vec![];
+    for keyword in keywords {
+        if !res.contains(keyword) {
+            res.push(keyword.to_owned());
+        }
+    }
+    res
+}
+
+/// Write `contents` through a temporary file in the same directory,
+/// then rename it to `full_path`, so that the file is never
+/// partially written. Permissions of an existing file are kept
+fn write_atomically(full_path: &Path, contents: &str) -> Result<()> {
+    let temp_file = write_temp_file(full_path, contents)?;
+    persist(temp_file, full_path)
+}
+
+/// First half of `write_atomically`: write `contents` to a temporary
+/// file next to `full_path`. The file is removed when dropped
+fn write_temp_file(full_path: &Path, contents: &str) -> Result<tempfile::NamedTempFile> {
+    let parent_path = full_path.parent().expect("full path should have a parent");
+    let mut temp_file = tempfile::NamedTempFile::new_in(parent_path).map_err(|source| Io {
+        message: format!("While creating temporary file in {parent_path:?}"),
+        source,
+    })?;
+    if let Ok(metadata) = std::fs::metadata(full_path) {
+        temp_file
+            .as_file()
+            .set_permissions(metadata.permissions())
+            .map_err(|source| Io {
+                message: format!("While setting permissions for {full_path:?}"),
+                source,
+            })?;
+    }
+    temp_file
+        .write_all(contents.as_bytes())
+        .map_err(|source| Io {
+            message: format!("While saving note in {full_path:?}"),
+            source,
+        })?;
+    Ok(temp_file)
+}
+
+/// Second half of `write_atomically`: move the temporary file to `full_path`
+fn persist(temp_file: tempfile::NamedTempFile, full_path: &Path) -> Result<()> {
+    temp_file.persist(full_path).map_err(|e| Io {
+        message: format!("While saving note in {full_path:?}"),
+        source: e.error,
+    })?;
+    Ok(())
+}
+
+/// Turn `keyword` into a valid keyword, made of lowercase ascii letters
+/// and digits only - `_` separates keywords in filenames, and `-` is used
+/// in slugs
+pub fn sanitize_keyword(keyword: &str) -> Result<String> {
+    let res: String = slug::slugify(keyword)
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect();
+    if res.is_empty() {
+        return Err(ParseError(format!("invalid keyword: '{keyword}'")));
+    }
+    Ok(res)
+}
+
+/// The keyword marking a note as a draft
+pub const DRAFT_KEYWORD: &str = "draft";
+
+/// Extensions of the files that are notes rather than attachments for
+/// `NotesRepository::attachments_for`, unless
+/// `RepositoryConfig::allowed_extensions` is set. Other files named after
+/// the id of a note are its attachments, like images or PDFs
+pub const NOTE_EXTENSIONS: &[&str] = &["md", "markdown", "org", "txt"];
+
+/// Added to the file name of the copies made by `Backup::Suffix`. These
+/// files are neither notes nor attachments
+pub const BACKUP_EXTENSION: &str = "bak";
+
+/// Used in the file name of notes whose slug is empty
+pub const UNTITLED_SLUG: &str = "untitled";
+
+/// Directory where drafts can be stored, next to the `<year>` directories.
+/// Notes in there must have the `draft` keyword
+const DRAFT_DIRECTORY: &str = "draft";
+
+/// The slug of a note without title is empty
+fn slugify_title(title: Option<&str>) -> String {
+    title.map(slug::slugify).unwrap_or_default()
+}
+
+fn is_year(name: &str) -> bool {
+    name.len() == 4 && name.chars().all(|c| c.is_ascii_digit())
+}
+
+#[derive(Clone)]
+/// What can be learned from the filename of a note - the title is
+/// only rebuilt from the slug, since the real one lives in the front matter
 pub struct FileInfo {
-    pub id: Id,
-    pub slug: String,
-    pub keywords: Vec<String>,
-    pub extension: String,
+    id: Id,
+    slug: String,
+    title: String,
+    keywords: Vec<String>,
+    extension: String,
+}
+
+impl FileInfo {
+    pub fn id(&self) -> &Id {
+        &self.id
+    }
+
+    pub fn slug(&self) -> &str {
+        &self.slug
+    }
+
+    /// A best-effort title, rebuilt from the slug: dashes are replaced by
+    /// spaces and the first letter is capitalized. This is lossy - case
+    /// and punctuation are lost - so use the title of the front matter
+    /// when it matters, for instance with `into_metadata_from_file`
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn keywords(&self) -> &[String] {
+        &self.keywords
+    }
+
+    pub fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    /// Build the metadata of the note, given its title.
+    /// As usual the slug of the metadata is derived from the title, and
+    /// the slug from the filename is kept in `Metadata::filename_slug`
+    pub fn into_metadata(self, title: Option<String>) -> Metadata {
+        // Note: the keywords were already normalized when parsing the file name
+        let mut metadata = Metadata::new_with_normalization(
+            self.id,
+            title,
+            self.keywords,
+            self.extension,
+            KeywordNormalization::Keep,
+        );
+        metadata.filename_slug = self.slug;
+        metadata
+    }
+
+    /// Same as `into_metadata`, for callers that already read the title
+    /// from the front matter
+    pub fn into_metadata_with_title(self, title: String) -> Metadata {
+        self.into_metadata(Some(title))
+    }
+
+    /// Read the front matter of the note at `full_path` - and only the
+    /// front matter - to build the metadata of the note
+    pub fn into_metadata_from_file(self, full_path: &Path) -> Result<Metadata> {
+        self.into_metadata_from_file_with_config(full_path, &RepositoryConfig::default())
+    }
+
+    /// Same as `into_metadata_from_file`, but normalize the keywords as
+    /// `config` says
+    pub fn into_metadata_from_file_with_config(
+        self,
+        full_path: &Path,
+        config: &RepositoryConfig,
+    ) -> Result<Metadata> {
+        let front_matter = read_front_matter_from_file(full_path, config)?;
+        self.into_metadata_from_front_matter(&front_matter, config)
+            .map_err(|e| with_path(e, full_path))
+    }
+
+    /// The title, keywords, aliases and date come from `front_matter`.
+    /// Fail if the identifier of the front matter does not match the id
+    fn into_metadata_from_front_matter(
+        self,
+        front_matter: &FrontMatter,
+        config: &RepositoryConfig,
+    ) -> Result<Metadata> {
+        if let Some(identifier) = front_matter.identifier() {
+            if identifier != self.id.as_str() {
+                return Err(ParseError(format!(
+                    "identifier {identifier} in front matter does not match id {} from the filename",
+                    self.id.as_str()
+                )));
+            }
+        }
+        let mut metadata = self.into_metadata(front_matter.title.clone());
+        // Note: keywords from the front matter win over the ones in the filename
+        metadata.keywords = config.normalize_keywords.apply(&front_matter.keywords);
+        metadata.aliases = front_matter.aliases.clone();
+        metadata.original_date = original_date(&metadata.id, front_matter);
+        Ok(metadata)
+    }
+}
+
+/// Same as `parse_file_name`, but also fail if the extension is not
+/// allowed by `config`, and normalize the keywords as `config` says
+pub fn parse_file_name_with_config(
+    path: impl AsRef<Path>,
+    config: &RepositoryConfig,
+) -> Result<FileInfo> {
+    let mut info = parse_file_name_keeping_case(path.as_ref())?;
+    info.keywords = config.normalize_keywords.apply(&info.keywords);
+    config.check_extension(info.extension())?;
+    Ok(info)
+}
+
+/// Parse the file name of a note. `path` can be a bare file name, or
+/// a relative or full path, in which case only its last component is used
+/// The keywords are trimmed and lowercased
+pub fn parse_file_name(path: impl AsRef<Path>) -> Result<FileInfo> {
+    parse_file_name_with_config(path, &RepositoryConfig::default())
 }
 
-pub fn parse_file_name(name: &str) -> Result<FileInfo> {
+fn parse_file_name_keeping_case(path: &Path) -> Result<FileInfo> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| ParseError(format!("{path:?} has no file name")))?
+        .to_string_lossy();
     let captures = FILENAME_RE
-        .captures(name)
+        .captures(&name)
         .ok_or_else(|| ParseError(format!("Filename {name} did not match expected regex")))?;
 
     let id = captures
@@ -93,35 +397,61 @@ pub fn parse_file_name(name: &str) -> Result<FileInfo> {
         .map(|x| x.to_string())
         .collect();
 
+    // Note: extensions are always stored in lowercase, so that
+    // notes ending with `.MD` are saved with `.md`
     let extension = captures
         .get(4)
         .expect("FILENAME_RE should contain the correct number of groups")
         .as_str()
-        .to_owned();
+        .to_lowercase();
+
+    let title = title_from_slug(&slug);
 
     Ok(FileInfo {
         id,
         slug,
+        title,
         keywords,
         extension,
     })
 }
 
-fn parse_front_matter(contents: &str) -> Result<(FrontMatter, String)> {
-    let docs: Vec<_> = contents.splitn(3, "---\n").collect();
-    if docs.is_empty() {
-        return Err(Error::ParseError("Missing front matter".to_string()));
+/// `this-is-a-title` -> `This is a title`
+fn title_from_slug(slug: &str) -> String {
+    let words = slug.replace('-', " ");
+    let mut chars = words.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => words,
+    }
+}
+
+/// Split `contents` into the front matter, between the two `---` lines
+/// at the top, and the text that follows it.
+/// The text is borrowed from `contents`, and is returned unchanged -
+/// including `\r\n` line endings, if any
+pub fn parse_front_matter(contents: &str) -> Result<(FrontMatter, &str)> {
+    let mut lines = contents.split_inclusive('\n');
+    let first_line = lines
+        .next()
+        .ok_or_else(|| ParseError("Missing front matter".to_string()))?;
+    if first_line.trim_end_matches(['\r', '\n']) != "---" {
+        return Err(ParseError("Missing front matter".to_string()));
     }
-    if docs.len() < 2 {
-        return Err(Error::ParseError("Unfinished front matter".to_string()));
+    let start = first_line.len();
+    let mut end = start;
+    for line in lines {
+        if line.trim_end_matches(['\r', '\n']) == "---" {
+            let yaml = contents[start..end].replace("\r\n", "\n");
+            let front_matter = FrontMatter::parse(&yaml)?;
+            return Ok((front_matter, &contents[end + line.len()..]));
+        }
+        end += line.len();
     }
-    let first_doc = &docs[1];
-    let text = docs[2];
-    let front_matter = FrontMatter::parse(first_doc)?;
-    Ok((front_matter, text.to_string()))
+    Err(ParseError("Unfinished front matter".to_string()))
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Deserialize, Serialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
 /// A new-type on top of String so that only valid Ids can
 /// be used
 /// As a reminder, the Id in denote is YYYYMMDDTHHMMSS
@@ -132,18 +462,66 @@ impl Id {
         self.0.as_str()
     }
 
+    /// The `YYYY` part of the id - also the name of the directory
+    /// containing the note
+    pub fn year(&self) -> &str {
+        &self.0[0..4]
+    }
+
+    /// The `MM` part of the id
+    pub fn month(&self) -> &str {
+        &self.0[4..6]
+    }
+
+    /// The `DD` part of the id
+    pub fn day(&self) -> &str {
+        &self.0[6..8]
+    }
+
+    /// Format the id as `YYYY-MM-DD HH:MM:SS`, or return it
+    /// unchanged if it does not contain a valid date
     pub fn human_date(&self) -> String {
-        let ymd = &self.0[0..8];
-        let year = &ymd[0..4];
-        let month = &ymd[4..6];
-        let day = &ymd[6..8];
+        let format = format_description!("[year]-[month]-[day] [hour]:[minute]:[second]");
+        match self.to_offset_date_time() {
+            Ok(date) => date
+                .format(&format)
+                .expect("format checked during compilation"),
+            Err(_) => self.0.clone(),
+        }
+    }
+
+    /// Format the id as a RFC 3339 timestamp, like `2022-07-07T14:27:08Z`,
+    /// or return it unchanged if it does not contain a valid date
+    pub fn to_rfc3339(&self) -> String {
+        match self.to_offset_date_time() {
+            Ok(date) => date
+                .format(&Rfc3339)
+                .expect("ids always fit in RFC 3339 timestamps"),
+            Err(_) => self.0.clone(),
+        }
+    }
 
-        let hms = &self.0[9..];
-        let hours = &hms[0..2];
-        let minutes = &hms[2..4];
-        let seconds = &hms[4..6];
+    /// Format the id according to `date_format`, or return it
+    /// unchanged if it does not contain a valid date
+    pub fn format_date(&self, date_format: DateFormat) -> String {
+        let date = match self.to_offset_date_time() {
+            Ok(date) => date,
+            Err(_) => return self.0.clone(),
+        };
+        let formatted = match date_format {
+            DateFormat::HumanReadable => return self.human_date(),
+            DateFormat::Iso8601 => return self.to_rfc3339(),
+            DateFormat::DateOnly => date.format(format_description!("[year]-[month]-[day]")),
+        };
+        formatted.expect("format checked during compilation")
+    }
 
-        format!("{year}-{month}-{day} {hours}:{minutes}:{seconds}")
+    /// The date of the id, assuming UTC
+    pub fn to_offset_date_time(&self) -> Result<OffsetDateTime> {
+        let format = format_description!("[year][month][day]T[hour][minute][second]");
+        let date = PrimitiveDateTime::parse(&self.0, &format)
+            .map_err(|e| ParseError(format!("id {} is not a valid date: {e}", self.0)))?;
+        Ok(date.assume_utc())
     }
 
     pub fn from_date(offsett_date_time: &OffsetDateTime) -> Self {
@@ -151,6 +529,24 @@ impl Id {
         let formatted_date = offsett_date_time.format(&format).unwrap();
         Self::from_str(&formatted_date).unwrap()
     }
+
+    /// Build an id from a number of seconds since the Unix epoch, in UTC.
+    /// Fail for dates before year 0 or after year 9999
+    pub fn from_unix_secs(secs: i64) -> Result<Self> {
+        let date = OffsetDateTime::from_unix_timestamp(secs)
+            .map_err(|e| ParseError(format!("invalid timestamp {secs}: {e}")))?;
+        if !(0..=9999).contains(&date.year()) {
+            return Err(ParseError(format!(
+                "timestamp {secs} is out of the range of ids"
+            )));
+        }
+        Ok(Self::from_date(&date))
+    }
+
+    /// The number of seconds since the Unix epoch, the id being in UTC
+    pub fn to_unix_secs(&self) -> Result<i64> {
+        Ok(self.to_offset_date_time()?.unix_timestamp())
+    }
 }
 
 impl FromStr for Id {
@@ -173,32 +569,256 @@ impl FromStr for Id {
             )));
         }
 
+        // Note: this makes sure the slices in `year()`, `month()` and `day()`
+        // fall on char boundaries
+        if !s.is_ascii() {
+            return Err(ParseError(format!(
+                "value '{s}' should only contain ascii characters"
+            )));
+        }
+
         Ok(Self(s.to_string()))
     }
 }
 
+impl std::fmt::Display for Id {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+/// How the `date` field of the front matter is written
+pub enum DateFormat {
+    /// `2024-01-15 14:30:00`
+    #[default]
+    HumanReadable,
+    /// `2024-01-15T14:30:00Z`
+    Iso8601,
+    /// `2024-01-15`
+    DateOnly,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// How the keywords of the notes are normalized - see
+/// `RepositoryConfig::normalize_keywords`
+pub enum KeywordNormalization {
+    /// Trim and lowercase the keywords, so that `Rust` and `rust` are
+    /// the same keyword, then remove duplicates
+    #[default]
+    Lowercase,
+    /// Same as `Lowercase`, and sort the keywords too
+    Sort,
+    /// Keep the keywords as written, for users who rely on their case
+    Keep,
+}
+
+impl KeywordNormalization {
+    pub fn apply(self, keywords: &[String]) -> Vec<String> {
+        if self == KeywordNormalization::Keep {
+            return keywords.to_vec();
+        }
+        let mut res: Vec<String> = keywords.iter().map(|k| k.trim().to_lowercase()).collect();
+        if self == KeywordNormalization::Sort {
+            res.sort();
+        }
+        deduplicate(&res)
+    }
+}
+
+impl FromStr for KeywordNormalization {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "lowercase" => Ok(KeywordNormalization::Lowercase),
+            "sort" => Ok(KeywordNormalization::Sort),
+            "keep" => Ok(KeywordNormalization::Keep),
+            _ => Err(ParseError(format!(
+                "invalid keyword normalization: '{s}' - expected lowercase, sort or keep"
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Where `NotesRepository::update` copies a note before renaming it
+pub enum Backup {
+    /// Next to the note, with `BACKUP_EXTENSION` added to its file name
+    Suffix,
+    /// In the given directory, keeping the `<year>` directories
+    Dir(PathBuf),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A change on disk that a repository in dry-run mode skipped - see
+/// `NotesRepository::take_planned_actions`
+pub enum PlannedAction {
+    Write(PathBuf),
+    Rename { from: PathBuf, to: PathBuf },
+    Delete(PathBuf),
+    Copy { from: PathBuf, to: PathBuf },
+}
+
+impl std::fmt::Display for PlannedAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlannedAction::Write(path) => write!(f, "would write {}", path.display()),
+            PlannedAction::Rename { from, to } => {
+                write!(f, "would rename {} to {}", from.display(), to.display())
+            }
+            PlannedAction::Delete(path) => write!(f, "would delete {}", path.display()),
+            PlannedAction::Copy { from, to } => {
+                write!(f, "would copy {} to {}", from.display(), to.display())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The slug in the file name of a note did not match its title when
+/// `NotesRepository::update` was called - see
+/// `NotesRepository::take_slug_mismatches`
+pub struct SlugMismatch {
+    pub path: PathBuf,
+    pub filename_slug: String,
+    /// The slug of the title, used in the new file name
+    pub slug: String,
+}
+
+impl std::fmt::Display for SlugMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Slug '{}' in {} does not match the title - the title wins, using '{}'",
+            self.filename_slug,
+            self.path.display(),
+            self.slug
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// Settings of a `NotesRepository`
+pub struct RepositoryConfig {
+    pub date_format: DateFormat,
+    /// Extensions of the notes, compared without case. Files with other
+    /// extensions are not listed, and cannot be loaded nor saved.
+    /// When not set, notes can have any extension - set it so that
+    /// attachments are not listed as notes
+    pub allowed_extensions: Option<Vec<String>>,
+    /// Applied to the keywords of the notes that are loaded, imported or
+    /// created. Trimmed and lowercased by default
+    pub normalize_keywords: KeywordNormalization,
+    /// Copy the notes before `update` renames them. Off by default
+    pub backup: Option<Backup>,
+}
+
+impl RepositoryConfig {
+    /// Fail unless notes can use the given extension
+    pub fn check_extension(&self, extension: &str) -> Result<()> {
+        if self.allows_extension(extension) {
+            Ok(())
+        } else {
+            Err(ParseError(format!(
+                "extension '{extension}' is not allowed"
+            )))
+        }
+    }
+
+    /// True if notes can use the given extension - always true when
+    /// `allowed_extensions` is not set
+    pub fn allows_extension(&self, extension: &str) -> bool {
+        match &self.allowed_extensions {
+            Some(allowed) => allowed.iter().any(|e| e.eq_ignore_ascii_case(extension)),
+            None => true,
+        }
+    }
+
+    /// `keyword` normalized as `normalize_keywords` says
+    pub fn normalize_keyword(&self, keyword: &str) -> String {
+        self.normalize_keywords
+            .apply(&[keyword.to_owned()])
+            .remove(0)
+    }
+
+    /// Make a keyword given by a user valid with `sanitize_keyword`,
+    /// then normalize it
+    pub fn sanitize_keyword(&self, keyword: &str) -> Result<String> {
+        Ok(self.normalize_keyword(&sanitize_keyword(keyword)?))
+    }
+
+    /// True if files with this extension are notes rather than attachments
+    pub fn is_note_extension(&self, extension: &str) -> bool {
+        match &self.allowed_extensions {
+            Some(allowed) => allowed.iter().any(|e| e.eq_ignore_ascii_case(extension)),
+            None => NOTE_EXTENSIONS
+                .iter()
+                .any(|e| e.eq_ignore_ascii_case(extension)),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 /// Contain all the metadata about a note.
 /// Some of it come from the front matter, like the title,
 /// but some other come from the filename, like the slug, the extension,
 /// or the keywords
+///
+/// When the slug in the filename and the title disagree, the title
+/// wins: `slug` is always derived from the title, and this is what
+/// is used to compute the path of the note. The slug that was read
+/// from the filename is kept in `filename_slug`, so that callers
+/// can detect that the file is about to be renamed
+///
+/// Notes without a title have an empty slug, and `UNTITLED_SLUG`
+/// is used in their file name instead
 pub struct Metadata {
     id: Id,
-    title: String,
+    title: Option<String>,
     slug: String,
+    filename_slug: String,
     keywords: Vec<String>,
     extension: String,
+    #[serde(default)]
+    aliases: Vec<String>,
+    /// The `date` of the front matter, when it was written in a format
+    /// that `DateFormat` cannot produce, like `2022-07-07T14:27:08+02:00`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    original_date: Option<String>,
 }
 
 impl Metadata {
-    pub fn new(id: Id, title: String, keywords: Vec<String>, extension: String) -> Metadata {
-        let slug = slug::slugify(&title);
+    /// Use `None` as the title for notes without one
+    /// The keywords are trimmed and lowercased
+    pub fn new(
+        id: Id,
+        title: impl Into<Option<String>>,
+        keywords: Vec<String>,
+        extension: String,
+    ) -> Metadata {
+        Self::new_with_normalization(id, title, keywords, extension, Default::default())
+    }
+
+    /// Same as `new`, but normalize the keywords with `normalization`
+    pub fn new_with_normalization(
+        id: Id,
+        title: impl Into<Option<String>>,
+        keywords: Vec<String>,
+        extension: String,
+        normalization: KeywordNormalization,
+    ) -> Metadata {
+        let title = title.into();
+        let slug = slugify_title(title.as_deref());
         Metadata {
             id,
             title,
+            filename_slug: slug.clone(),
             slug,
-            keywords,
-            extension,
+            keywords: normalization.apply(&keywords),
+            extension: extension.to_lowercase(),
+            aliases: vec![],
+            original_date: None,
         }
     }
 
@@ -210,8 +830,16 @@ impl Metadata {
         self.slug.as_ref()
     }
 
-    pub fn title(&self) -> &str {
-        self.title.as_ref()
+    /// The slug found in the filename when the note was loaded - same
+    /// as `slug` for notes that were not loaded from disk
+    pub fn filename_slug(&self) -> &str {
+        self.filename_slug.as_ref()
+    }
+
+    /// `None` when the note has no title, which is not the same
+    /// as an empty title
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
     }
 
     pub fn extension(&self) -> &str {
@@ -222,11 +850,63 @@ impl Metadata {
         &self.keywords
     }
 
+    /// Other names of the note, from the `aliases` field of the front matter
+    pub fn aliases(&self) -> &[String] {
+        &self.aliases
+    }
+
+    pub fn created_at(&self) -> Result<OffsetDateTime> {
+        self.id.to_offset_date_time()
+    }
+
+    pub fn has_keyword(&self, keyword: &str) -> bool {
+        self.keywords.iter().any(|k| k == keyword)
+    }
+
+    /// Remove duplicated keywords, keeping the first occurrence
+    pub fn deduplicate_keywords(&mut self) {
+        self.keywords = deduplicate(&self.keywords);
+    }
+
+    /// Add a keyword, unless it is already there.
+    /// Keywords are sorted afterwards
+    pub fn add_keyword(&mut self, keyword: &str) {
+        if self.has_keyword(keyword) {
+            return;
+        }
+        self.keywords.push(keyword.to_owned());
+        self.keywords.sort();
+    }
+
+    pub fn remove_keyword(&mut self, keyword: &str) {
+        self.keywords.retain(|k| k != keyword);
+    }
+
+    /// True if the note has the reserved `draft` keyword
+    pub fn is_draft(&self) -> bool {
+        self.has_keyword(DRAFT_KEYWORD)
+    }
+
+    /// Remove the `draft` keyword, and make sure the slug matches the title
+    pub fn promote(&mut self) {
+        self.remove_keyword(DRAFT_KEYWORD);
+        self.slug = slugify_title(self.title.as_deref());
+    }
+
     pub fn front_matter(&self) -> FrontMatter {
+        self.front_matter_with_format(DateFormat::default())
+    }
+
+    pub fn front_matter_with_format(&self, date_format: DateFormat) -> FrontMatter {
         FrontMatter {
-            title: self.title.to_owned(),
-            date: self.id.human_date(),
-            keywords: self.keywords.join(" "),
+            title: self.title.clone(),
+            date: match &self.original_date {
+                Some(date) => date.clone(),
+                None => self.id.format_date(date_format),
+            },
+            keywords: self.keywords.clone(),
+            identifier: Some(self.id.as_str().to_owned()),
+            aliases: self.aliases.clone(),
         }
     }
 
@@ -234,59 +914,232 @@ impl Metadata {
         let Metadata {
             id,
             keywords,
-            slug,
             extension,
             ..
         } = self;
 
+        let year_path = Path::new(id.year());
         let id = id.as_str();
-        let year = &id[0..4];
-        let year_path = PathBuf::from_str(year).expect("year should be ascii");
 
         let keywords = keywords.join("_");
+        let slug = self.slug_in_file_name();
+
+        // Note: the file name never contains a separator, and `join` uses
+        // the separator of the current platform
+        year_path.join(format!("{id}--{slug}__{keywords}.{extension}"))
+    }
 
-        let file_path = PathBuf::from_str(&format!("{id}--{slug}__{keywords}.{extension}"))
-            .expect("filename should be valid utf-8");
+    /// The slug, or `UNTITLED_SLUG` for notes without a title
+    fn slug_in_file_name(&self) -> &str {
+        if self.slug.is_empty() {
+            UNTITLED_SLUG
+        } else {
+            &self.slug
+        }
+    }
+}
 
-        year_path.join(file_path)
+/// `<id> -- <title> [kw1, kw2]`
+impl std::fmt::Display for Metadata {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} -- {} [{}]",
+            self.id,
+            self.title.as_deref().unwrap_or_default(),
+            self.keywords.join(", ")
+        )
     }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
 /// The front matter of a note.
 /// Currently using YAML
-/// Note that `keywords` is list of words separated by spaces,
-/// which is find because we don't allow spaces in keywords.
+/// Note that `keywords` is read either from a list of words separated by
+/// spaces - which is fine because we don't allow spaces in keywords - or
+/// from a YAML list. It is always written as a space-separated string.
 ///
-/// The title may not be set
+/// The title may not be set: an empty `title:` field is read as `None`,
+/// while `title: ''` is an empty title
+///
+/// Like upstream denote, the id of the note is written in
+/// the `identifier` field, but notes without it are still accepted
 pub struct FrontMatter {
-    title: String,
+    #[serde(default)]
+    title: Option<String>,
     date: String,
-    keywords: String,
+    #[serde(
+        alias = "tags",
+        deserialize_with = "deserialize_words",
+        serialize_with = "serialize_words"
+    )]
+    keywords: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    identifier: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_words",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    aliases: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum WordsOrList {
+    Words(String),
+    List(Vec<String>),
+}
+
+/// Accept either a space-separated string or a YAML list
+fn deserialize_words<'de, D>(deserializer: D) -> std::result::Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(match WordsOrList::deserialize(deserializer)? {
+        WordsOrList::Words(words) => words.split_whitespace().map(|w| w.to_owned()).collect(),
+        WordsOrList::List(list) => list.into_iter().filter(|w| !w.is_empty()).collect(),
+    })
+}
+
+/// Write the words as a space-separated string
+fn serialize_words<S>(words: &[String], serializer: S) -> std::result::Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_str(&words.join(" "))
 }
 
 impl FrontMatter {
-    pub fn title(&self) -> &str {
-        &self.title
+    /// A front matter without identifier nor aliases
+    pub fn new(title: Option<String>, date: String, keywords: Vec<String>) -> Self {
+        Self {
+            title,
+            date,
+            keywords,
+            identifier: None,
+            aliases: vec![],
+        }
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    pub fn identifier(&self) -> Option<&str> {
+        self.identifier.as_deref()
+    }
+
+    /// Written either as a space-separated string or as a YAML list
+    pub fn aliases(&self) -> &[String] {
+        &self.aliases
     }
 
+    /// The keywords. Notes without keywords get an empty list
     pub fn keywords(&self) -> Vec<String> {
-        self.keywords.split(' ').map(|x| x.to_string()).collect()
+        self.keywords.clone()
+    }
+
+    /// Remove duplicated keywords, keeping the first occurrence
+    pub fn deduplicate_keywords(&mut self) {
+        self.keywords = deduplicate(&self.keywords);
+    }
+
+    /// The keywords, lowercased, sorted and without duplicates
+    pub fn sorted_keywords(&self) -> Vec<String> {
+        KeywordNormalization::Sort.apply(&self.keywords)
+    }
+
+    /// Replace the keywords by `sorted_keywords()`
+    pub fn normalize(&mut self) {
+        self.keywords = self.sorted_keywords();
     }
 
     pub fn dump(&self) -> String {
-        serde_yaml::to_string(self).expect("front matter should always be serializable")
+        let dumped =
+            serde_yaml::to_string(self).expect("front matter should always be serializable");
+        // serde_yaml does not quote the identifier, but upstream denote does
+        match &self.identifier {
+            Some(identifier) => dumped.replace(
+                &format!("identifier: {identifier}\n"),
+                &format!("identifier: \"{identifier}\"\n"),
+            ),
+            None => dumped,
+        }
     }
 
     pub fn slug(&self) -> String {
-        slug::slugify(&self.title)
+        slugify_title(self.title.as_deref())
+    }
+
+    /// Combine two front matters:
+    /// * the title of `self` is used, unless it is empty
+    /// * the earlier of the two dates is used - a date that can be parsed
+    ///   wins over one that cannot, and `self` wins when neither can
+    /// * keywords and aliases are the union of both, in order, without duplicates
+    /// * the identifier of `self` is kept
+    pub fn merge_with(&self, other: &FrontMatter) -> FrontMatter {
+        let title = if self.title.as_deref().unwrap_or_default().trim().is_empty() {
+            other.title.clone()
+        } else {
+            self.title.clone()
+        };
+        let date = match (self.date_as_id(), other.date_as_id()) {
+            (Ok(mine), Ok(theirs)) if theirs < mine => other.date.clone(),
+            (Err(_), Ok(_)) => other.date.clone(),
+            _ => self.date.clone(),
+        };
+        let keywords: Vec<String> = self
+            .keywords()
+            .into_iter()
+            .chain(other.keywords())
+            .filter(|k| !k.is_empty())
+            .collect();
+        let aliases: Vec<String> = self
+            .aliases
+            .iter()
+            .chain(other.aliases.iter())
+            .cloned()
+            .collect();
+        FrontMatter {
+            title,
+            date,
+            keywords: deduplicate(&keywords),
+            identifier: self.identifier.clone(),
+            aliases: deduplicate(&aliases),
+        }
+    }
+
+    /// Parse the `date` field, written with any of the `DateFormat`s.
+    /// Dates with an offset are converted to UTC, and dates without
+    /// time are assumed to be at midnight
+    pub fn date_as_id(&self) -> Result<Id> {
+        let date = &self.date;
+        if let Ok(parsed) = OffsetDateTime::parse(date, &Rfc3339) {
+            return Ok(Id::from_date(&parsed.to_offset(UtcOffset::UTC)));
+        }
+        let human_readable = format_description!("[year]-[month]-[day] [hour]:[minute]:[second]");
+        if let Ok(parsed) = PrimitiveDateTime::parse(date, &human_readable) {
+            return Ok(Id::from_date(&parsed.assume_utc()));
+        }
+        let date_only = format_description!("[year]-[month]-[day]");
+        if let Ok(parsed) = Date::parse(date, &date_only) {
+            return Ok(Id::from_date(&parsed.midnight().assume_utc()));
+        }
+        Err(ParseError(format!("Could not parse date '{date}'")))
+    }
+
+    /// Same as `parse`, but normalize the keywords as `config` says
+    pub fn parse_with_config(front_matter: &str, config: &RepositoryConfig) -> Result<Self> {
+        let mut res = Self::parse(front_matter)?;
+        res.keywords = config.normalize_keywords.apply(&res.keywords);
+        Ok(res)
     }
 
     pub fn parse(front_matter: &str) -> Result<Self> {
-        serde_yaml::from_str(front_matter).map_err(|e| {
-            ParseError(format!(
-                "could not deserialize front matter\n{front_matter}\n{e})"
-            ))
+        serde_yaml::from_str(front_matter).map_err(|source| Yaml {
+            message: format!("could not deserialize front matter\n{front_matter}"),
+            source,
         })
     }
 }
@@ -295,14 +1148,31 @@ impl FrontMatter {
 /// A Note has some metadata and some text
 /// Note that the metada is different from the frontmatter, it does
 /// contain exacly the same data
+///
+/// The text always ends with exactly one newline (unless it is empty),
+/// so that dumped notes end with a newline, and dumping a loaded
+/// note gives back the same contents
 pub struct Note {
     metadata: Metadata,
     text: String,
 }
 
+fn normalize_text(text: String) -> String {
+    let text = text.replace("\r\n", "\n");
+    let trimmed = text.trim_end_matches('\n');
+    if trimmed.is_empty() {
+        String::new()
+    } else {
+        format!("{trimmed}\n")
+    }
+}
+
 impl Note {
     pub fn new(metadata: Metadata, text: String) -> Self {
-        Self { metadata, text }
+        Self {
+            metadata,
+            text: normalize_text(text),
+        }
     }
 
     fn relative_path(&self) -> PathBuf {
@@ -315,49 +1185,541 @@ impl Note {
 
     /// Update the metadata when the front matter changes
     pub fn update(&mut self, front_matter: &FrontMatter) {
-        self.metadata.title = front_matter.title.to_string();
+        self.metadata.title = front_matter.title.clone();
         self.metadata.slug = front_matter.slug();
         self.metadata.keywords = front_matter.keywords();
+        self.metadata.aliases = front_matter.aliases.clone();
+        self.metadata.original_date = original_date(&self.metadata.id, front_matter);
     }
 
     pub fn metadata(&self) -> &Metadata {
         &self.metadata
     }
 
-    pub fn id(&self) -> &str {
-        self.metadata.id()
+    pub fn text(&self) -> &str {
+        &self.text
     }
 
-    pub fn dump(&self) -> String {
-        let mut res = String::new();
-        // Note: serde_yaml writes a leading `---`
-        let front_matter = self.metadata.front_matter();
-        res.push_str(&front_matter.dump());
+    /// The text of the note, after the front matter. This is the same as
+    /// `text()`, and is guaranteed never to contain the front matter block
+    /// itself - it may still contain `---` lines used as horizontal rules
+    pub fn body_text(&self) -> &str {
+        self.text()
+    }
+
+    /// Same as `dump()`, without the leading `---` line
+    pub fn as_markdown(&self) -> String {
+        let dumped = self.dump();
+        match dumped.strip_prefix("---\n") {
+            Some(rest) => rest.to_owned(),
+            None => dumped,
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        self.metadata.id()
+    }
+
+    pub fn has_keyword(&self, keyword: &str) -> bool {
+        self.metadata.has_keyword(keyword)
+    }
+
+    pub fn add_keyword(&mut self, keyword: &str) {
+        self.metadata.add_keyword(keyword)
+    }
+
+    pub fn remove_keyword(&mut self, keyword: &str) {
+        self.metadata.remove_keyword(keyword)
+    }
+
+    pub fn is_draft(&self) -> bool {
+        self.metadata.is_draft()
+    }
+
+    pub fn promote(&mut self) {
+        self.metadata.promote()
+    }
+
+    /// The creation date of the note, from its id
+    pub fn created_at(&self) -> Result<OffsetDateTime> {
+        self.metadata.id.to_offset_date_time()
+    }
+
+    pub fn dump(&self) -> String {
+        self.dump_with_format(DateFormat::default())
+    }
+
+    /// Add `section` at the end of the text, separated from the
+    /// existing text by an empty line
+    pub fn append_text(&mut self, section: &str) {
+        let section = section.trim_end_matches('\n');
+        self.text = if self.text.is_empty() {
+            normalize_text(section.to_owned())
+        } else {
+            normalize_text(format!("{}\n{section}", self.text))
+        };
+    }
+
+    /// Add `section` at the beginning of the text, separated from the
+    /// existing text by an empty line
+    pub fn prepend_text(&mut self, section: &str) {
+        let section = section.trim_end_matches('\n');
+        self.text = if self.text.is_empty() {
+            normalize_text(section.to_owned())
+        } else {
+            normalize_text(format!("{section}\n\n{}", self.text))
+        };
+    }
+
+    /// Merge `other` into a copy of this note: the front matters are
+    /// combined with `FrontMatter::merge_with`, and the text of `other`
+    /// is appended after an horizontal rule. The id of `self` is kept
+    pub fn merge_bodies(&self, other: &Note) -> Note {
+        let front_matter = self.front_matter().merge_with(&other.front_matter());
+        let text = match (self.text.is_empty(), other.text.is_empty()) {
+            (_, true) => self.text.clone(),
+            (true, false) => other.text.clone(),
+            (false, false) => format!("{}\n---\n\n{}", self.text, other.text),
+        };
+        let mut note = Note::new(self.metadata.clone(), text);
+        note.update(&front_matter);
+        note
+    }
+
+    /// A new note with the same title, keywords, extension and text,
+    /// but with the given id. Aliases and the original date are not
+    /// copied, since they belong to this note
+    pub fn clone_with_new_id(&self, new_id: Id) -> Note {
+        // Note: the keywords were already normalized for this note
+        let metadata = Metadata::new_with_normalization(
+            new_id,
+            self.metadata.title.clone(),
+            self.metadata.keywords.clone(),
+            self.metadata.extension.clone(),
+            KeywordNormalization::Keep,
+        );
+        Note::new(metadata, self.text.clone())
+    }
+
+    /// Same as `clone_with_new_id`, with an id generated from the
+    /// current time
+    pub fn clone_with_now(&self) -> Note {
+        self.clone_with_new_id(Id::from_date(&OffsetDateTime::now_utc()))
+    }
+
+    /// All the links in the text of the note, in order of appearance
+    pub fn extract_links(&self) -> Vec<NoteLink> {
+        let mut res = vec![];
+        for captures in WIKILINK_RE.captures_iter(&self.text) {
+            let start = captures.get(0).expect("group 0 always matches").start();
+            let target = captures[1].trim().to_string();
+            res.push((start, NoteLink::Wikilink { target }));
+        }
+        for captures in MARKDOWN_LINK_RE.captures_iter(&self.text) {
+            let start = captures.get(0).expect("group 0 always matches").start();
+            let text = captures[1].to_string();
+            let url = captures[2].to_string();
+            res.push((start, NoteLink::MarkdownLink { text, url }));
+        }
+        for captures in AUTOLINK_RE.captures_iter(&self.text) {
+            let start = captures.get(0).expect("group 0 always matches").start();
+            let url = captures[1].to_string();
+            res.push((start, NoteLink::Autolink { url }));
+        }
+        res.sort_by_key(|(start, _)| *start);
+        res.into_iter().map(|(_, link)| link).collect()
+    }
+
+    /// Number of whitespace-separated words in the text, without the
+    /// front matter. Nothing is skipped: the contents of code fences,
+    /// and the fences themselves, are counted as words too
+    pub fn word_count(&self) -> usize {
+        self.text.split_whitespace().count()
+    }
+
+    /// Time needed to read the text at `wpm` words per minute, rounded
+    /// up to the next minute - 0 for empty notes, or when `wpm` is 0
+    pub fn reading_time_minutes(&self, wpm: u32) -> u32 {
+        if wpm == 0 {
+            return 0;
+        }
+        let words = self.word_count() as u32;
+        words.div_ceil(wpm)
+    }
+
+    /// Hex-encoded SHA-256 of `dump()` - stable across runs and
+    /// platforms, so it can be stored to detect changes later on
+    pub fn content_hash(&self) -> String {
+        let digest = Sha256::digest(self.dump().as_bytes());
+        digest.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    /// Same as `dump`, but choose how the date of the front matter
+    /// is written
+    pub fn dump_with_format(&self, date_format: DateFormat) -> String {
+        let mut res = String::new();
+        // Note: serde_yaml writes a leading `---`
+        let front_matter = self.metadata.front_matter_with_format(date_format);
+        res.push_str(&front_matter.dump());
         res.push_str("---\n");
         res.push_str(&self.text);
         res
     }
 }
 
-fn get_note_from_markdown(id: Id, contents: String) -> Result<Note> {
+impl std::fmt::Display for Note {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.metadata)
+    }
+}
+
+#[derive(Debug, Default)]
+/// Build a `Note` step by step:
+///
+/// ```
+/// # use std::str::FromStr;
+/// # use denote::{Id, NoteBuilder};
+/// let note = NoteBuilder::new()
+///     .id(Id::from_str("20220707T142708").unwrap())
+///     .title("This is a title")
+///     .keyword("k1")
+///     .keyword("k2")
+///     .text("This is my note")
+///     .build()
+///     .unwrap();
+/// ```
+///
+/// The id and the title are required, the extension defaults to `md`
+/// and the text to an empty string
+pub struct NoteBuilder {
+    id: Option<Id>,
+    title: Option<String>,
+    keywords: Vec<String>,
+    normalize_keywords: KeywordNormalization,
+    extension: Option<String>,
+    text: String,
+}
+
+impl NoteBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn id(mut self, id: Id) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = Some(title.to_owned());
+        self
+    }
+
+    /// Add a keyword - can be called several times
+    pub fn keyword(mut self, keyword: &str) -> Self {
+        self.keywords.push(keyword.to_owned());
+        self
+    }
+
+    /// How the keywords are normalized - see `RepositoryConfig::normalize_keywords`
+    pub fn normalize_keywords(mut self, normalization: KeywordNormalization) -> Self {
+        self.normalize_keywords = normalization;
+        self
+    }
+
+    pub fn extension(mut self, extension: &str) -> Self {
+        self.extension = Some(extension.to_owned());
+        self
+    }
+
+    pub fn text(mut self, text: &str) -> Self {
+        self.text = text.to_owned();
+        self
+    }
+
+    pub fn build(self) -> Result<Note> {
+        let id = self
+            .id
+            .ok_or_else(|| ParseError("note id is required".to_string()))?;
+        let title = self
+            .title
+            .ok_or_else(|| ParseError("note title is required".to_string()))?;
+        let extension = self.extension.unwrap_or_else(|| "md".to_string());
+        let metadata = Metadata::new_with_normalization(
+            id,
+            title,
+            self.keywords,
+            extension,
+            self.normalize_keywords,
+        );
+        Ok(Note::new(metadata, self.text))
+    }
+}
+
+fn get_note_from_markdown(
+    id: Id,
+    contents: String,
+    normalization: KeywordNormalization,
+) -> Result<Note> {
     let (front_matter, text) = parse_front_matter(&contents)?;
-    let title = front_matter.title.to_string();
+    Ok(note_from_front_matter(
+        id,
+        &front_matter,
+        text.to_string(),
+        normalization,
+    ))
+}
+
+fn note_from_front_matter(
+    id: Id,
+    front_matter: &FrontMatter,
+    text: String,
+    normalization: KeywordNormalization,
+) -> Note {
+    Note::new(
+        metadata_from_front_matter(id, front_matter, normalization),
+        text,
+    )
+}
+
+fn metadata_from_front_matter(
+    id: Id,
+    front_matter: &FrontMatter,
+    normalization: KeywordNormalization,
+) -> Metadata {
+    let title = front_matter.title.clone();
     let slug = front_matter.slug();
-    let keywords = front_matter.keywords();
-    let metadata = Metadata {
+    let keywords = normalization.apply(&front_matter.keywords);
+    Metadata {
         id,
         title,
+        filename_slug: slug.clone(),
         slug,
         keywords,
         extension: "md".to_string(),
-    };
-    Ok(Note { metadata, text })
+        aliases: front_matter.aliases.clone(),
+        original_date: None,
+    }
+}
+
+/// The date of the front matter, if it should be kept as is when
+/// the note is dumped - that is, if it can't be generated from the id
+fn original_date(id: &Id, front_matter: &FrontMatter) -> Option<String> {
+    let generated = [
+        DateFormat::HumanReadable,
+        DateFormat::Iso8601,
+        DateFormat::DateOnly,
+    ]
+    .map(|f| id.format_date(f));
+    if generated.contains(&front_matter.date) {
+        None
+    } else {
+        Some(front_matter.date.clone())
+    }
+}
+
+/// Prefix the message of parse and io errors with `path`, since
+/// errors about the contents of a note do not say which note it was
+fn with_path(error: Error, path: &Path) -> Error {
+    match error {
+        ParseError(message) => ParseError(format!("{path:?}: {message}")),
+        Yaml { message, source } => Yaml {
+            message: format!("{path:?}: {message}"),
+            source,
+        },
+        Io { message, source } => Io {
+            message: format!("{path:?}: {message}"),
+            source,
+        },
+        e => e,
+    }
+}
+
+fn read_front_matter_from_file(full_path: &Path, config: &RepositoryConfig) -> Result<FrontMatter> {
+    let file = std::fs::File::open(full_path).map_err(|source| Io {
+        message: format!("While loading note from {full_path:?}"),
+        source,
+    })?;
+    read_front_matter(BufReader::new(file), config).map_err(|e| with_path(e, full_path))
+}
+
+/// Read the front matter at the beginning of `reader`, and stop
+/// reading right after the closing `---`
+fn read_front_matter(reader: impl BufRead, config: &RepositoryConfig) -> Result<FrontMatter> {
+    let mut lines = reader.lines();
+    match lines.next() {
+        Some(Ok(line)) if line.trim_start_matches('\u{FEFF}').trim_end_matches('\r') == "---" => {}
+        Some(Err(source)) => {
+            return Err(Io {
+                message: "While reading front matter".to_string(),
+                source,
+            })
+        }
+        _ => return Err(ParseError("Missing front matter".to_string())),
+    }
+    let mut yaml = String::new();
+    for line in lines {
+        let line = line.map_err(|source| Io {
+            message: "While reading front matter".to_string(),
+            source,
+        })?;
+        let line = line.trim_end_matches('\r');
+        if line == "---" {
+            return FrontMatter::parse_with_config(&yaml, config);
+        }
+        yaml.push_str(line);
+        yaml.push('\n');
+    }
+    Err(ParseError("Unfinished front matter".to_string()))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+/// A link found in the text of a note
+pub enum NoteLink {
+    /// `[[target]]` - the target is usually the id or the slug of an other note
+    Wikilink { target: String },
+    /// `[text](url)`
+    MarkdownLink { text: String, url: String },
+    /// `<url>`
+    Autolink { url: String },
+}
+
+impl NoteLink {
+    /// True if the link refers to the note with the given id and slug:
+    /// wikilinks must use the id or the slug as target, and markdown links
+    /// must contain the id in their url
+    pub fn points_to(&self, id: &Id, slug: &str) -> bool {
+        match self {
+            NoteLink::Wikilink { target } => target == id.as_str() || target == slug,
+            NoteLink::MarkdownLink { url, .. } => url.contains(id.as_str()),
+            NoteLink::Autolink { .. } => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+/// The kind of problems found by `NotesRepository::validate`
+pub enum IssueKind {
+    /// The front matter is missing or cannot be parsed, or the note
+    /// cannot be loaded for an other reason
+    InvalidNote,
+    /// The path of the note does not match its title or keywords
+    WrongPath,
+    /// The note is not in the `<year>` directory matching its id
+    MisfiledYear,
+    /// The `identifier` of the front matter is not the id of the filename
+    IdMismatch,
+    /// An other note has the same id
+    DuplicateId,
+    /// Other notes have the same slug
+    DuplicateSlug,
+    /// The note has no keyword at all
+    EmptyKeywords,
+    /// The note is in the `draft` directory, but does not have
+    /// the `draft` keyword
+    MissingDraftKeyword,
+}
+
+impl std::fmt::Display for IssueKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IssueKind::InvalidNote => write!(f, "invalid note"),
+            IssueKind::WrongPath => write!(f, "wrong path"),
+            IssueKind::MisfiledYear => write!(f, "misfiled year"),
+            IssueKind::IdMismatch => write!(f, "id mismatch"),
+            IssueKind::DuplicateId => write!(f, "duplicate id"),
+            IssueKind::DuplicateSlug => write!(f, "duplicate slug"),
+            IssueKind::EmptyKeywords => write!(f, "empty keywords"),
+            IssueKind::MissingDraftKeyword => write!(f, "missing draft keyword"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// A problem found by `NotesRepository::validate`
+/// Paths are relative to the base path of the repository
+pub struct ValidationIssue {
+    pub path: PathBuf,
+    pub kind: IssueKind,
+    pub description: String,
+    /// Where to move the note to fix the problem, if renaming is enough
+    pub suggested_new_path: Option<PathBuf>,
+}
+
+impl ValidationIssue {
+    fn new(path: PathBuf, kind: IssueKind, description: impl Into<String>) -> Self {
+        ValidationIssue {
+            path,
+            kind,
+            description: description.into(),
+            suggested_new_path: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+/// Summary of a repository, returned by `NotesRepository::statistics`
+pub struct RepoStats {
+    pub total_notes: usize,
+    /// Always 0 when the statistics were computed in fast mode
+    pub total_words: usize,
+    /// Size of all the note files
+    pub total_bytes: u64,
+    /// Years of the ids of the notes, sorted
+    pub years: Vec<u16>,
+    /// Number of notes created each year
+    pub notes_per_year: BTreeMap<u16, usize>,
+    pub unique_keywords: usize,
+    /// Keywords with the number of notes using them, most used first,
+    /// then in alphabetical order
+    pub top_keywords: Vec<(String, usize)>,
+    /// When several keywords are used by the same number of notes,
+    /// the first one in alphabetical order is used
+    pub most_common_keyword: Option<String>,
+}
+
+/// Called with `(current, total)` during batch operations
+pub type ProgressCallback = Box<dyn Fn(usize, usize) + Send + Sync>;
+
+/// Source of the current time, used to generate the ids of new notes
+/// Tests can use their own implementation to get predictable ids
+pub trait Clock: Send + Sync {
+    fn now(&self) -> OffsetDateTime;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+/// The default clock, using the system time in UTC
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> OffsetDateTime {
+        OffsetDateTime::now_utc()
+    }
 }
 
-#[derive(Debug)]
 /// Store the notes with the proper file names inside a `base_path`
 pub struct NotesRepository {
     base_path: PathBuf,
+    index: Option<Index>,
+    config: RepositoryConfig,
+    progress: Option<ProgressCallback>,
+    clock: Arc<dyn Clock>,
+    dry_run: bool,
+    planned_actions: RefCell<Vec<PlannedAction>>,
+    slug_mismatches: RefCell<Vec<SlugMismatch>>,
+}
+
+impl std::fmt::Debug for NotesRepository {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NotesRepository")
+            .field("base_path", &self.base_path)
+            .field("index", &self.index)
+            .field("config", &self.config)
+            .field("dry_run", &self.dry_run)
+            .finish_non_exhaustive()
+    }
 }
 
 impl NotesRepository {
@@ -374,9 +1736,126 @@ impl NotesRepository {
         }
         Ok(NotesRepository {
             base_path: base_path.to_owned(),
+            index: None,
+            config: RepositoryConfig::default(),
+            progress: None,
+            clock: Arc::new(SystemClock),
+            dry_run: false,
+            planned_actions: RefCell::new(vec![]),
+            slug_mismatches: RefCell::new(vec![]),
         })
     }
 
+    /// Report progress of `load_all`, `export_to_json`,
+    /// `rename_keyword_globally` and `repair_filenames` to `callback`
+    pub fn with_progress(mut self, callback: ProgressCallback) -> Self {
+        self.progress = Some(callback);
+        self
+    }
+
+    fn report_progress(&self, current: usize, total: usize) {
+        if let Some(callback) = &self.progress {
+            callback(current, total)
+        }
+    }
+
+    /// With `dry_run`, nothing is written, renamed or deleted on disk -
+    /// `save`, `update`, `rename_keyword_globally`, `repair_filenames` and
+    /// `delete` still run their checks, but only record what they would do.
+    /// See `take_planned_actions`
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// The changes skipped in dry-run mode since the last call, in order
+    pub fn take_planned_actions(&self) -> Vec<PlannedAction> {
+        self.planned_actions.take()
+    }
+
+    fn plan(&self, action: PlannedAction) {
+        self.planned_actions.borrow_mut().push(action);
+    }
+
+    /// The notes whose slug was replaced by the one of the title by
+    /// `update` since the last call, in order
+    pub fn take_slug_mismatches(&self) -> Vec<SlugMismatch> {
+        self.slug_mismatches.take()
+    }
+
+    /// Use `clock` instead of the system time when generating ids
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// The current time, according to the clock of the repository
+    pub fn now(&self) -> OffsetDateTime {
+        self.clock.now()
+    }
+
+    /// Change the settings of the repository
+    pub fn with_config(mut self, config: RepositoryConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Only treat files with the given extensions as notes - see
+    /// `RepositoryConfig::allowed_extensions`
+    pub fn with_extensions(mut self, extensions: &[&str]) -> Self {
+        let extensions = extensions.iter().map(|e| e.to_string()).collect();
+        self.config.allowed_extensions = Some(extensions);
+        self
+    }
+
+    /// Copy the notes before `update` renames them - see
+    /// `RepositoryConfig::backup`
+    pub fn with_backup(mut self, backup: Backup) -> Self {
+        self.config.backup = Some(backup);
+        self
+    }
+
+    pub fn config(&self) -> &RepositoryConfig {
+        &self.config
+    }
+
+    /// Like `open`, but use the SQLite index stored in `base_path`
+    /// for `count`, `find_by_keyword` and `filter_by_date_range`.
+    /// The index is built if it does not exist yet, and is kept
+    /// up to date by `save` and `update`
+    pub fn open_indexed(base_path: impl AsRef<Path>) -> Result<Self> {
+        let mut repository = Self::open(base_path)?;
+        let db_path = repository.base_path.join(index::INDEX_FILE_NAME);
+        if !db_path.exists() {
+            repository.build_index()?;
+        }
+        repository.index = Some(Index::open(&repository.base_path)?);
+        Ok(repository)
+    }
+
+    /// (Re)create the `denote.db` SQLite index in `base_path`
+    pub fn build_index(&self) -> Result<()> {
+        let index = Index::open(&self.base_path)?;
+        index.rebuild(&self.base_path, &self.note_paths()?, &self.config)
+    }
+
+    /// Like `open`, but create `base_path` (and its parents) when
+    /// it does not exist yet
+    pub fn open_or_create(base_path: impl AsRef<Path>) -> Result<Self> {
+        let base_path = base_path.as_ref();
+        if !base_path.exists() {
+            std::fs::create_dir_all(base_path).map_err(|source| Io {
+                message: format!("While creating {base_path:?}"),
+                source,
+            })?;
+        }
+        Self::open(base_path)
+    }
+
     /// The base path of the repository, where the `<year>` directories
     /// are created
     pub fn base_path(&self) -> &Path {
@@ -385,168 +1864,3688 @@ impl NotesRepository {
 
     /// Import a plain md file and save it with the correct name
     /// Called by cli::new_note
+    /// If a note already uses the id for the current time, the id
+    /// is bumped by one second until it is free
     pub fn import_from_markdown(&self, markdown_path: &Path) -> Result<PathBuf> {
-        let contents = std::fs::read_to_string(markdown_path)
-            .map_err(|e| Error::OSError(format!("while reading: {markdown_path:#?}: {e}")))?;
-        let now = OffsetDateTime::now_utc();
-        let id = Id::from_date(&now);
+        let contents = std::fs::read_to_string(markdown_path).map_err(|source| Io {
+            message: format!("while reading: {markdown_path:#?}"),
+            source,
+        })?;
+        let id = self.next_free_id(self.now())?;
 
-        let note = get_note_from_markdown(id, contents)
+        let note = get_note_from_markdown(id, contents, self.config.normalize_keywords)
             .map_err(|e| Error::OSError(format!("invalid contents for {markdown_path:#?}: {e}")))?;
         self.save(&note)
     }
 
+    /// The id for `date`, or for the first second after it that is not
+    /// used by any note yet
+    fn next_free_id(&self, date: OffsetDateTime) -> Result<Id> {
+        let mut date = date;
+        loop {
+            let id = Id::from_date(&date);
+            if !self.has_id(&id)? {
+                return Ok(id);
+            }
+            date += time::Duration::seconds(1);
+        }
+    }
+
+    /// Import all the `.md` files directly inside `dir`, in alphabetical order
+    /// Return the paths of the saved notes
+    ///
+    /// Since the ids are made of the current time and have a resolution of one
+    /// second, importing several files with `import_from_markdown` in a row would
+    /// give them the same id. Instead, the first file gets the current time, and
+    /// each of the next ones gets an id one second after the previous one.
+    /// Ids already used by other notes are skipped
+    pub fn import_dir(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        let entries = std::fs::read_dir(dir).map_err(|source| Io {
+            message: format!("While reading {dir:?}"),
+            source,
+        })?;
+        let mut markdown_paths = vec![];
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+            if path.is_file() && path.extension().is_some_and(|e| e == "md") {
+                markdown_paths.push(path);
+            }
+        }
+        markdown_paths.sort();
+
+        let mut date = self.now();
+        let mut res = vec![];
+        for markdown_path in &markdown_paths {
+            let contents = std::fs::read_to_string(markdown_path).map_err(|source| Io {
+                message: format!("while reading: {markdown_path:#?}"),
+                source,
+            })?;
+            let id = self.next_free_id(date)?;
+            date = id.to_offset_date_time()? + time::Duration::seconds(1);
+            let note = get_note_from_markdown(id, contents, self.config.normalize_keywords)
+                .map_err(|e| {
+                    Error::OSError(format!("invalid contents for {markdown_path:#?}: {e}"))
+                })?;
+            res.push(self.save(&note)?);
+        }
+        Ok(res)
+    }
+
     /// To be called when the markdown file has changed - this will
     /// handle the rename automatically - note that the ID won't change,
     /// this is by design
+    /// When `RepositoryConfig::backup` is set, the note is copied before
+    /// being renamed
+    /// When the slug in the file name and the title disagree - whichever of
+    /// the two was edited - the title wins: the file name is changed to use
+    /// the slug of the title, and the mismatch is recorded - see
+    /// `take_slug_mismatches`
     /// Return the new note path (which may have changed)
     pub fn update(&self, relative_path: &Path) -> Result<PathBuf> {
         let full_path = &self.base_path.join(relative_path);
         let note = self.load(relative_path)?;
+        let metadata = note.metadata();
+        if metadata.filename_slug() != metadata.slug_in_file_name() {
+            self.slug_mismatches.borrow_mut().push(SlugMismatch {
+                path: relative_path.to_path_buf(),
+                filename_slug: metadata.filename_slug().to_owned(),
+                slug: metadata.slug_in_file_name().to_owned(),
+            });
+        }
 
         let new_relative_path = note.relative_path();
         let new_full_path = &self.base_path.join(&new_relative_path);
         if full_path != new_full_path {
-            println!("{full_path:#?} -> {new_full_path:#?}");
-            std::fs::rename(full_path, new_full_path)
-                .map_err(|e| Error::OSError(format!("Could not rename note: {e}")))?;
+            if !self.dry_run {
+                println!("{full_path:#?} -> {new_full_path:#?}");
+            }
+            if let Some(backup) = &self.config.backup {
+                self.backup_note(relative_path, backup)?;
+            }
+            self.rename(relative_path, &new_relative_path)?;
         }
 
         Ok(new_full_path.to_path_buf())
     }
 
-    /// Load a note file
-    pub fn load(&self, relative_path: &Path) -> Result<Note> {
-        if !relative_path.is_relative() {
+    /// Move a note inside the repository, creating the `<year>` directory
+    /// when needed. Refuse to overwrite an existing note
+    fn rename(&self, old_relative_path: &Path, new_relative_path: &Path) -> Result<()> {
+        let full_path = self.base_path.join(old_relative_path);
+        let new_full_path = self.base_path.join(new_relative_path);
+        if new_full_path.exists() {
             return Err(OSError(format!(
-                "Expecting a relative path when loading, get {relative_path:+?}"
+                "Cannot rename {full_path:?} to {new_full_path:?}: destination already exists"
             )));
         }
-        let full_path = &self.base_path.join(relative_path);
-        let contents = std::fs::read_to_string(full_path)
-            .map_err(|e| OSError(format!("While loading note from {full_path:?}: {e}")))?;
+        if self.dry_run {
+            self.plan(PlannedAction::Rename {
+                from: old_relative_path.to_path_buf(),
+                to: new_relative_path.to_path_buf(),
+            });
+            return Ok(());
+        }
+        let parent_path = new_full_path
+            .parent()
+            .expect("full path should have a parent");
+        std::fs::create_dir_all(parent_path).map_err(|source| Io {
+            message: format!("While creating {parent_path:?}"),
+            source,
+        })?;
+        std::fs::rename(&full_path, &new_full_path).map_err(|source| Io {
+            message: "Could not rename note".to_string(),
+            source,
+        })?;
+        if let Some(index) = &self.index {
+            index.replace(
+                &self.base_path,
+                Some(old_relative_path),
+                new_relative_path,
+                &self.config,
+            )?;
+        }
+        Ok(())
+    }
 
-        let file_name = &name_from_relative_path(relative_path);
-        let info = parse_file_name(file_name)?;
-        get_note_from_markdown(info.id, contents)
+    /// Write `note` at `relative_path`, then move it to the path matching
+    /// its metadata if required. Unlike `update`, the note is not read
+    /// back from the disk, so this also works in dry-run mode
+    /// Return the new full path of the note
+    pub(crate) fn write_and_rename(&self, relative_path: &Path, note: &Note) -> Result<PathBuf> {
+        self.write_note(relative_path, note)?;
+        let new_relative_path = note.relative_path();
+        if new_relative_path != relative_path {
+            self.rename(relative_path, &new_relative_path)?;
+        }
+        Ok(self.base_path.join(new_relative_path))
     }
 
-    /// Save a note in the repository
-    /// Create `<year>` directory when needed
-    pub fn save(&self, note: &Note) -> Result<PathBuf> {
-        let relative_path = &note.relative_path();
-        let full_path = &self.base_path.join(relative_path);
+    /// Run all the health checks on every note, without stopping at the
+    /// first problem: front matter that cannot be parsed, paths that do not
+    /// match the metadata, notes in the wrong `<year>` directory, identifiers
+    /// that do not match the filename, duplicate ids and slugs, notes without
+    /// keywords, and drafts without the `draft` keyword.
+    /// When renaming the note is enough, the issue has a suggested new path
+    pub fn validate(&self) -> Result<Vec<ValidationIssue>> {
+        let mut res = vec![];
+        let mut seen_ids: HashMap<Id, PathBuf> = HashMap::new();
+        for relative_path in self.note_paths()? {
+            let path = relative_path.clone();
+            let info = parse_file_name_with_config(&relative_path, &self.config)?;
+            let id = info.id().as_str();
 
-        let parent_path = full_path.parent().expect("full path should have a parent");
+            match seen_ids.get(info.id()) {
+                Some(first) => res.push(ValidationIssue::new(
+                    path.clone(),
+                    IssueKind::DuplicateId,
+                    format!("id {id} is already used by {}", first.display()),
+                )),
+                None => {
+                    seen_ids.insert(info.id().clone(), relative_path.clone());
+                }
+            }
 
-        if parent_path.exists() {
-            if parent_path.is_file() {
-                return Err(OSError(format!(
-                    "Cannot use {parent_path:?} as year path because there's a file here)"
-                )));
+            let year_dir = relative_path
+                .parent()
+                .map(|p| p.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let misfiled = year_dir != info.id().year();
+            let front_matter = match self.read_front_matter(&relative_path) {
+                Ok(front_matter) => front_matter,
+                Err(e) => {
+                    res.push(ValidationIssue::new(
+                        path.clone(),
+                        IssueKind::InvalidNote,
+                        e.to_string(),
+                    ));
+                    if misfiled {
+                        let name = name_from_relative_path(&relative_path);
+                        res.push(ValidationIssue {
+                            suggested_new_path: Some(Path::new(info.id().year()).join(name)),
+                            ..ValidationIssue::new(
+                                path,
+                                IssueKind::MisfiledYear,
+                                format!("id {id} should be in {}", info.id().year()),
+                            )
+                        });
+                    }
+                    continue;
+                }
+            };
+            let metadata = info
+                .clone()
+                .into_metadata_from_front_matter(&front_matter, &self.config)
+                .map_err(|e| with_path(e, &self.base_path.join(&relative_path)));
+            match metadata {
+                Err(e) => res.push(ValidationIssue::new(
+                    path.clone(),
+                    IssueKind::InvalidNote,
+                    e.to_string(),
+                )),
+                Ok(metadata) => {
+                    let expected_path = metadata.relative_path();
+                    if expected_path != relative_path {
+                        let (kind, description) = if misfiled {
+                            (
+                                IssueKind::MisfiledYear,
+                                format!("id {id} should be in {}", info.id().year()),
+                            )
+                        } else {
+                            (
+                                IssueKind::WrongPath,
+                                "file name does not match the front matter".to_string(),
+                            )
+                        };
+                        res.push(ValidationIssue {
+                            suggested_new_path: Some(expected_path),
+                            ..ValidationIssue::new(path.clone(), kind, description)
+                        });
+                    }
+                }
+            }
+            if let Some(identifier) = front_matter.identifier() {
+                if identifier != id {
+                    res.push(ValidationIssue::new(
+                        path.clone(),
+                        IssueKind::IdMismatch,
+                        format!("identifier {identifier} in front matter does not match id {id}"),
+                    ));
+                }
+            }
+            if front_matter.keywords().is_empty() {
+                res.push(ValidationIssue::new(
+                    path,
+                    IssueKind::EmptyKeywords,
+                    "no keywords",
+                ));
             }
-        } else {
-            println!("Creating {parent_path:?}");
-            std::fs::create_dir_all(&parent_path).map_err(|e| {
-                OSError(format!(
-                    "While creating parent path {parent_path:?}for note :{e}"
-                ))
-            })?;
         }
 
-        let to_write = note.dump();
+        let mut duplicates: Vec<_> = self.find_duplicate_slugs()?.into_iter().collect();
+        duplicates.sort();
+        for (slug, paths) in duplicates {
+            for path in paths {
+                res.push(ValidationIssue::new(
+                    path,
+                    IssueKind::DuplicateSlug,
+                    format!("slug {slug} is used by several notes"),
+                ));
+            }
+        }
 
-        std::fs::write(full_path, &to_write)
-            .map_err(|e| OSError(format!("While saving note in {full_path:?}: {e}")))?;
-        Ok(relative_path.to_path_buf())
+        for name in self.note_names_in_year(DRAFT_DIRECTORY)? {
+            let relative_path = Path::new(DRAFT_DIRECTORY).join(name);
+            let issue = match self.load_metadata(&relative_path) {
+                Err(e) => {
+                    ValidationIssue::new(relative_path, IssueKind::InvalidNote, e.to_string())
+                }
+                Ok(metadata) if !metadata.is_draft() => ValidationIssue::new(
+                    relative_path,
+                    IssueKind::MissingDraftKeyword,
+                    format!("notes in {DRAFT_DIRECTORY}/ should have the {DRAFT_KEYWORD} keyword"),
+                ),
+                Ok(_) => continue,
+            };
+            res.push(issue);
+        }
+        Ok(res)
     }
-}
-
-#[cfg(test)]
-mod tests {
 
-    use super::*;
+    /// Move the notes whose `<year>` directory does not match their id.
+    /// Only the directory changes - use `repair_filenames` to also fix
+    /// the filenames
+    /// Return the `(old, new)` relative paths of the moved notes
+    pub fn repair(&self) -> Result<Vec<(PathBuf, PathBuf)>> {
+        let mut res = vec![];
+        for relative_path in self.note_paths()? {
+            let name = name_from_relative_path(&relative_path);
+            let info = parse_file_name_with_config(&name, &self.config)?;
+            let expected_path = Path::new(info.id().year()).join(&name);
+            if expected_path != relative_path {
+                self.rename(&relative_path, &expected_path)?;
+                res.push((relative_path, expected_path));
+            }
+        }
+        Ok(res)
+    }
 
-    #[test]
-    fn test_id_sorting() {
-        let id1 = Id::from_str("20220707T142708").unwrap();
-        let id2 = Id::from_str("2022070lT142709").unwrap();
-        let id3 = Id::from_str("2022070lT142709").unwrap();
+    /// Rename all the notes that have a suggested path in the
+    /// issues returned by `validate`
+    /// Return the `(old, new)` relative paths of the renamed notes
+    pub fn repair_filenames(&self) -> Result<Vec<(PathBuf, PathBuf)>> {
+        let renames: Vec<_> = self
+            .validate()?
+            .into_iter()
+            .filter_map(|w| w.suggested_new_path.map(|new_path| (w.path, new_path)))
+            .collect();
+        let total = renames.len();
+        for (i, (old_path, new_path)) in renames.iter().enumerate() {
+            self.rename(old_path, new_path)?;
+            self.report_progress(i + 1, total);
+        }
+        Ok(renames)
+    }
 
-        assert_eq!(id2, id3);
-        assert!(id1 < id2)
+    /// Load all the notes of the repository, sorted by path
+    pub fn load_all(&self) -> Result<Vec<Note>> {
+        let relative_paths = self.note_paths()?;
+        let total = relative_paths.len();
+        let mut res = vec![];
+        for (i, relative_path) in relative_paths.iter().enumerate() {
+            res.push(self.load(relative_path)?);
+            self.report_progress(i + 1, total);
+        }
+        Ok(res)
     }
 
-    fn make_note() -> Note {
-        let id = Id::from_str("20220707T142708").unwrap();
-        let slug = "this-is-a-title".to_owned();
-        let title = "This is a title".to_owned();
-        let keywords = vec!["k1".to_owned(), "k2".to_owned()];
-        let extension = "md".to_owned();
-        let metadata = Metadata {
-            id,
-            slug,
-            title,
-            keywords,
-            extension,
-        };
+    /// All the notes of the repository, as a JSON array
+    pub fn export_to_json(&self) -> Result<String> {
+        let notes = self.load_all()?;
+        Ok(serde_json::to_string_pretty(&notes).expect("notes should always be serializable"))
+    }
 
-        Note {
-            metadata,
-            text: "This is my note".to_owned(),
+    /// Write the notes to `dest` as newline-delimited JSON, one note per
+    /// line, loading them one at a time
+    /// Return the number of written notes
+    pub fn export_ndjson(&self, dest: &mut dyn Write) -> Result<usize> {
+        let relative_paths = self.note_paths()?;
+        let total = relative_paths.len();
+        for (i, relative_path) in relative_paths.iter().enumerate() {
+            let note = self.load(relative_path)?;
+            let json = serde_json::to_string(&note).expect("notes should always be serializable");
+            writeln!(dest, "{json}").map_err(|source| Io {
+                message: "While writing JSON".to_string(),
+                source,
+            })?;
+            self.report_progress(i + 1, total);
         }
+        Ok(total)
+    }
+
+    /// Replace the keyword `old` by `new` in all the notes,
+    /// renaming them accordingly. Both keywords are normalized, and
+    /// `new` is sanitized first - see `RepositoryConfig::sanitize_keyword`
+    /// Return the new relative paths of the modified notes
+    pub fn rename_keyword_globally(&self, old: &str, new: &str) -> Result<Vec<PathBuf>> {
+        let old = &self.config.normalize_keyword(old);
+        let new = &self.config.sanitize_keyword(new)?;
+        let relative_paths = self.find_by_keyword(old)?;
+        let total = relative_paths.len();
+        let mut res = vec![];
+        for (i, relative_path) in relative_paths.iter().enumerate() {
+            let mut note = self.load(relative_path)?;
+            let keywords: Vec<String> = note
+                .metadata
+                .keywords
+                .iter()
+                .map(|k| {
+                    if k == old {
+                        new.to_owned()
+                    } else {
+                        k.to_owned()
+                    }
+                })
+                .collect();
+            note.metadata.keywords = deduplicate(&keywords);
+            let new_path = self.save(&note)?;
+            if &new_path != relative_path {
+                self.delete(relative_path)?;
+            }
+            res.push(new_path);
+            self.report_progress(i + 1, total);
+        }
+        Ok(res)
+    }
+
+    /// Remove a note from the repository, as well as its `<year>`
+    /// directory if it becomes empty
+    pub fn delete(&self, relative_path: &Path) -> Result<()> {
+        let full_path = self.base_path.join(relative_path);
+        if self.dry_run {
+            self.plan(PlannedAction::Delete(relative_path.to_path_buf()));
+            return Ok(());
+        }
+        std::fs::remove_file(&full_path).map_err(|source| Io {
+            message: format!("While removing {full_path:?}"),
+            source,
+        })?;
+        if let Some(index) = &self.index {
+            index.remove(relative_path)?;
+        }
+        self.prune_empty_year_directories()?;
+        Ok(())
+    }
+
+    /// Move the note at `relative_path` to an other repository, keeping
+    /// its id. The note is only deleted from `self` once it has been saved
+    /// in `dest_repo`, and nothing is done if `dest_repo` already has a
+    /// note with the same id - see `copy_note` to duplicate a note in the
+    /// same repository instead
+    /// Return the relative path of the note in `dest_repo`
+    pub fn move_note(&self, relative_path: &Path, dest_repo: &NotesRepository) -> Result<PathBuf> {
+        if dest_repo.base_path == self.base_path {
+            return Err(OSError(
+                "Cannot move a note to the repository it is in".to_string(),
+            ));
+        }
+        let note = self.load(relative_path)?;
+        if dest_repo.find_path_by_id(&note.metadata.id)?.is_some() {
+            return Err(OSError(format!(
+                "A note with id {} already exists in {:?}",
+                note.metadata.id, dest_repo.base_path
+            )));
+        }
+        let new_path = dest_repo.save(&note)?;
+        self.delete(relative_path)?;
+        Ok(new_path)
+    }
+
+    /// Duplicate the note at `relative_path` in this repository, with
+    /// a new id made of the current time, or of the first second after
+    /// it that is not used yet. Unlike `move_note`, the original note
+    /// is left untouched
+    /// Return the relative path of the copy
+    pub fn copy_note(&self, relative_path: &Path) -> Result<PathBuf> {
+        let note = self.load(relative_path)?;
+        let id = self.next_free_id(self.now())?;
+        self.save(&note.clone_with_new_id(id))
+    }
+
+    /// Same as `copy_note`, for the note with the given id. The copy gets
+    /// the same title, keywords and text, but its id and date are "now"
+    /// Return the relative path of the copy
+    pub fn duplicate(&self, id: &Id) -> Result<PathBuf> {
+        let relative_path = self
+            .find_path_by_id(id)?
+            .ok_or_else(|| OSError(format!("No note found with id {}", id.as_str())))?;
+        self.copy_note(&relative_path)
+    }
+
+    /// Remove the `<year>` directories that contain nothing at all
+    /// Return their relative paths
+    pub fn prune_empty_year_directories(&self) -> Result<Vec<PathBuf>> {
+        let mut res = vec![];
+        for year in self.years()? {
+            let year_path = self.base_path.join(&year);
+            let mut entries = std::fs::read_dir(&year_path).map_err(|source| Io {
+                message: format!("While reading {year_path:?}"),
+                source,
+            })?;
+            if entries.next().is_some() {
+                continue;
+            }
+            std::fs::remove_dir(&year_path).map_err(|source| Io {
+                message: format!("While removing {year_path:?}"),
+                source,
+            })?;
+            res.push(PathBuf::from(year));
+        }
+        Ok(res)
+    }
+
+    /// Load a note file
+    pub fn load(&self, relative_path: &Path) -> Result<Note> {
+        if !relative_path.is_relative() {
+            return Err(OSError(format!(
+                "Expecting a relative path when loading, get {relative_path:+?}"
+            )));
+        }
+        let info = parse_file_name_with_config(relative_path, &self.config)?;
+        let full_path = &self.base_path.join(relative_path);
+        let contents = std::fs::read_to_string(full_path).map_err(|source| Io {
+            message: format!("While loading note from {full_path:?}"),
+            source,
+        })?;
+
+        // Note: some Windows tools start files with a byte order mark
+        let contents = contents.trim_start_matches('\u{FEFF}');
+        let (front_matter, text) =
+            parse_front_matter(contents).map_err(|e| with_path(e, full_path))?;
+        let metadata = info
+            .into_metadata_from_front_matter(&front_matter, &self.config)
+            .map_err(|e| with_path(e, full_path))?;
+        Ok(Note::new(metadata, text.to_string()))
+    }
+
+    /// Same as `load`, but with a path that starts with the base path
+    /// of the repository
+    pub fn load_absolute(&self, full_path: &Path) -> Result<Note> {
+        let relative_path = full_path.strip_prefix(&self.base_path).map_err(|_| {
+            OSError(format!(
+                "{full_path:?} is not inside the repository at {:?}",
+                self.base_path
+            ))
+        })?;
+        let escapes = relative_path
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_)));
+        if escapes {
+            return Err(OSError(format!(
+                "{full_path:?} is not inside the repository at {:?}",
+                self.base_path
+            )));
+        }
+        self.load(relative_path)
+    }
+
+    /// Load the metadata of a note. The id, the extension and the filename
+    /// slug come from the filename, but the file is still opened since the
+    /// title only lives in the front matter.
+    /// The file is only read up to the end of the front matter, which is
+    /// much faster than `load` for notes with a long text
+    pub fn load_metadata(&self, relative_path: &Path) -> Result<Metadata> {
+        if !relative_path.is_relative() {
+            return Err(OSError(format!(
+                "Expecting a relative path when loading, get {relative_path:+?}"
+            )));
+        }
+        let info = parse_file_name_with_config(relative_path, &self.config)?;
+        info.into_metadata_from_file_with_config(&self.base_path.join(relative_path), &self.config)
+    }
+
+    /// Read the front matter of the note, normalizing its keywords if
+    /// the config says so
+    fn read_front_matter(&self, relative_path: &Path) -> Result<FrontMatter> {
+        read_front_matter_from_file(&self.base_path.join(relative_path), &self.config)
+    }
+
+    /// The metadata of all the notes, sorted by path
+    pub fn list_metadata(&self) -> Result<Vec<Metadata>> {
+        self.note_paths()?
+            .iter()
+            .map(|p| self.load_metadata(p))
+            .collect()
+    }
+
+    /// The metadata of the first note (sorted by path) having the given alias
+    pub fn get_by_alias(&self, alias: &str) -> Result<Option<Metadata>> {
+        for metadata in self.iter() {
+            let metadata = metadata?;
+            if metadata.aliases.iter().any(|a| a == alias) {
+                return Ok(Some(metadata));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The metadata of the notes with the given title
+    pub fn find_by_title(&self, title: &str) -> Result<Vec<Metadata>> {
+        let mut res = self.list_metadata()?;
+        res.retain(|m| m.title() == Some(title));
+        Ok(res)
+    }
+
+    /// The metadata of the notes whose title loosely matches `query`,
+    /// best matches first. See `fuzzy_score` for how matches are scored.
+    /// Notes without a title never match
+    pub fn search_title(&self, query: &str) -> Result<Vec<Metadata>> {
+        let mut scored = vec![];
+        for metadata in self.list_metadata()? {
+            let score = metadata.title().and_then(|t| fuzzy_score(query, t));
+            if let Some(score) = score {
+                scored.push((score, metadata));
+            }
+        }
+        scored.sort_by(|(a, _), (b, _)| b.cmp(a));
+        Ok(scored.into_iter().map(|(_, m)| m).collect())
+    }
+
+    /// The first note (sorted by path) whose filename contains the given slug.
+    /// Only the matching note is loaded
+    pub fn find_by_slug(&self, slug: &str) -> Result<Option<Note>> {
+        match self.note_paths_by_slug(slug)?.first() {
+            Some(relative_path) => Ok(Some(self.load(relative_path)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// All the notes whose filename contains the given slug, sorted by path -
+    /// slugs are not unique since two notes can have the same title
+    pub fn find_all_by_slug(&self, slug: &str) -> Result<Vec<Note>> {
+        self.note_paths_by_slug(slug)?
+            .iter()
+            .map(|p| self.load(p))
+            .collect()
+    }
+
+    fn note_paths_by_slug(&self, slug: &str) -> Result<Vec<PathBuf>> {
+        self.filter_by_filename(|info| info.slug() == slug)
+    }
+
+    /// Relative paths of the notes sharing the same slug, by slug.
+    /// Only the filenames are read, and notes without a title are ignored
+    pub fn find_duplicate_slugs(&self) -> Result<HashMap<String, Vec<PathBuf>>> {
+        let mut by_slug: HashMap<String, Vec<PathBuf>> = HashMap::new();
+        for relative_path in self.note_paths()? {
+            let info = parse_file_name_with_config(&relative_path, &self.config)?;
+            if info.slug() == UNTITLED_SLUG {
+                continue;
+            }
+            by_slug
+                .entry(info.slug().to_owned())
+                .or_default()
+                .push(relative_path);
+        }
+        by_slug.retain(|_, paths| paths.len() > 1);
+        Ok(by_slug)
+    }
+
+    /// Concatenate the given notes, sorted by id, in a single markdown
+    /// document. Each note starts with its title as a heading, followed
+    /// by an horizontal rule and its text
+    pub fn export_combined(&self, ids: &[Id]) -> Result<String> {
+        let mut ids = ids.to_vec();
+        ids.sort();
+        let mut res = String::new();
+        for id in &ids {
+            let relative_path = self
+                .find_path_by_id(id)?
+                .ok_or_else(|| OSError(format!("No note found with id {}", id.as_str())))?;
+            let note = self.load(&relative_path)?;
+            if !res.is_empty() {
+                res.push('\n');
+            }
+            let title = note.metadata.title().unwrap_or_default();
+            res.push_str(&format!("# {title}\n\n---\n\n"));
+            res.push_str(&note.text);
+        }
+        Ok(res)
+    }
+
+    /// The metadata of the notes in the given `<year>` directory, newest first.
+    /// Only this directory is read - and it is fine if it does not exist
+    pub fn notes_in_year(&self, year: u16) -> Result<Vec<Metadata>> {
+        let year = format!("{year:04}");
+        let mut res = self
+            .note_paths_in_year(&year)?
+            .iter()
+            .map(|p| self.load_metadata(p))
+            .collect::<Result<Vec<_>>>()?;
+        res.sort_by(|a, b| b.id.cmp(&a.id));
+        Ok(res)
+    }
+
+    /// Metadata of the notes whose id is between `start` and `end`
+    /// (both included), oldest first - see `filter_by_date_range`
+    pub fn notes_between(&self, start: &Id, end: &Id) -> Result<Vec<Metadata>> {
+        self.filter_by_date_range(start, end)?
+            .iter()
+            .map(|p| self.load_metadata(p))
+            .collect()
+    }
+
+    /// The links found in every note, by id of the note containing them
+    pub fn build_link_graph(&self) -> Result<HashMap<Id, Vec<NoteLink>>> {
+        let mut res = HashMap::new();
+        for relative_path in self.note_paths()? {
+            let note = self.load(&relative_path)?;
+            let links = note.extract_links();
+            res.insert(note.metadata.id, links);
+        }
+        Ok(res)
+    }
+
+    /// Replace the `denote:<old>` links by `denote:<new>` in the text of
+    /// all the notes - for instance after merging two notes
+    /// Return the relative paths of the changed notes, sorted
+    pub fn relink(&self, old: &Id, new: &Id) -> Result<Vec<PathBuf>> {
+        let old_link = format!("denote:{old}");
+        let new_link = format!("denote:{new}");
+        let mut res = vec![];
+        for relative_path in self.note_paths()? {
+            let mut note = self.load(&relative_path)?;
+            if !note.text.contains(&old_link) {
+                continue;
+            }
+            note.text = note.text.replace(&old_link, &new_link);
+            self.write_note(&relative_path, &note)?;
+            res.push(relative_path);
+        }
+        Ok(res)
+    }
+
+    /// Relative paths of the notes linking to the note with the given id,
+    /// sorted
+    pub fn backlinks_for(&self, id: &Id) -> Result<Vec<PathBuf>> {
+        let relative_path = self
+            .find_path_by_id(id)?
+            .ok_or_else(|| OSError(format!("No note found with id {}", id.as_str())))?;
+        let metadata = self.load_metadata(&relative_path)?;
+        let mut res = vec![];
+        for other_path in self.note_paths()? {
+            if other_path == relative_path {
+                continue;
+            }
+            let note = self.load(&other_path)?;
+            if note
+                .extract_links()
+                .iter()
+                .any(|l| l.points_to(id, &metadata.slug))
+            {
+                res.push(other_path);
+            }
+        }
+        Ok(res)
+    }
+
+    /// Same as `backlinks_for`, but for all the notes at once - every
+    /// note is only loaded once.
+    /// Notes without backlinks are mapped to an empty list
+    pub fn build_backlink_map(&self) -> Result<HashMap<Id, Vec<PathBuf>>> {
+        let mut notes = vec![];
+        for relative_path in self.note_paths()? {
+            let note = self.load(&relative_path)?;
+            let links = note.extract_links();
+            notes.push((relative_path, note.metadata, links));
+        }
+        let mut res = HashMap::new();
+        for (target_path, target, _) in &notes {
+            let backlinks = notes
+                .iter()
+                .filter(|(path, _, links)| {
+                    path != target_path
+                        && links.iter().any(|l| l.points_to(&target.id, &target.slug))
+                })
+                .map(|(path, _, _)| path.clone())
+                .collect();
+            res.insert(target.id.clone(), backlinks);
+        }
+        Ok(res)
+    }
+
+    /// Names of the `<year>` directories of the repository, sorted
+    fn years(&self) -> Result<Vec<String>> {
+        let mut res = vec![];
+        let entries = std::fs::read_dir(&self.base_path).map_err(|source| Io {
+            message: format!("While reading {:?}", self.base_path),
+            source,
+        })?;
+        for entry in entries {
+            let entry = entry?;
+            let year = entry.file_name().to_string_lossy().into_owned();
+            if entry.path().is_dir() && is_year(&year) {
+                res.push(year);
+            }
+        }
+        res.sort();
+        Ok(res)
+    }
+
+    /// File names of the notes in the given `<year>` directory, sorted.
+    /// Only files whose name match the denote naming convention are
+    /// returned - and nothing at all if the directory does not exist
+    fn note_names_in_year(&self, year: &str) -> Result<Vec<String>> {
+        let mut res = vec![];
+        let year_path = self.base_path.join(year);
+        if !year_path.is_dir() {
+            return Ok(res);
+        }
+        let notes = std::fs::read_dir(&year_path).map_err(|source| Io {
+            message: format!("While reading {year_path:?}"),
+            source,
+        })?;
+        for note in notes {
+            let note = note?;
+            let name = note.file_name().to_string_lossy().into_owned();
+            if note.path().is_file() && FILENAME_RE.is_match(&name) {
+                let extension = Path::new(&name).extension().unwrap_or_default();
+                let extension = extension.to_string_lossy();
+                if extension != BACKUP_EXTENSION && self.config.allows_extension(&extension) {
+                    res.push(name);
+                }
+            }
+        }
+        res.sort();
+        Ok(res)
+    }
+
+    /// Relative paths of all the notes in the repository, sorted.
+    /// Only files inside `<year>` directories whose name match the
+    /// denote naming convention are returned
+    fn note_paths(&self) -> Result<Vec<PathBuf>> {
+        let mut res = vec![];
+        for year in self.years()? {
+            res.extend(self.note_paths_in_year(&year)?);
+        }
+        Ok(res)
+    }
+
+    /// Relative paths of the notes in the given `<year>` directory, sorted
+    fn note_paths_in_year(&self, year: &str) -> Result<Vec<PathBuf>> {
+        let names = self.note_names_in_year(year)?;
+        Ok(names.iter().map(|n| Path::new(year).join(n)).collect())
+    }
+
+    /// Iterate over the metadata of all the notes, sorted by path.
+    /// The `<year>` directories are read one at a time, and each note
+    /// is only loaded when the iterator reaches it.
+    /// Errors are yielded as `Err` items, so a single invalid note does
+    /// not stop the iteration
+    pub fn iter(&self) -> impl Iterator<Item = Result<Metadata>> + '_ {
+        let years: Vec<Result<String>> = match self.years() {
+            Ok(years) => years.into_iter().map(Ok).collect(),
+            Err(e) => vec![Err(e)],
+        };
+        years
+            .into_iter()
+            .flat_map(move |year| -> Vec<Result<PathBuf>> {
+                match year.and_then(|y| self.note_paths_in_year(&y)) {
+                    Ok(paths) => paths.into_iter().map(Ok).collect(),
+                    Err(e) => vec![Err(e)],
+                }
+            })
+            .map(move |relative_path| relative_path.and_then(|p| self.load_metadata(&p)))
+    }
+
+    /// True if there is no note at all in the repository.
+    /// Stops at the first `<year>` directory containing a note
+    pub fn is_empty(&self) -> Result<bool> {
+        for year in self.years()? {
+            if !self.note_names_in_year(&year)?.is_empty() {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// True if there is a file at `relative_path` - note that the file
+    /// is not parsed
+    pub fn has_note(&self, relative_path: &Path) -> bool {
+        self.base_path.join(relative_path).is_file()
+    }
+
+    /// True if there is a note with the given id. Only the `<year>`
+    /// directory matching the id is read
+    pub fn has_id(&self, id: &Id) -> Result<bool> {
+        Ok(self.find_path_by_id(id)?.is_some())
+    }
+
+    /// Relative path of the note with the given id, if any.
+    /// Only the `<year>` directory matching the id is read
+    fn find_path_by_id(&self, id: &Id) -> Result<Option<PathBuf>> {
+        let year = id.year();
+        let id = id.as_str();
+        let names = self.note_names_in_year(year)?;
+        Ok(names
+            .into_iter()
+            .find(|n| n.starts_with(id))
+            .map(|n| Path::new(year).join(n)))
+    }
+
+    /// The `n` notes whose files were modified last, most recent first,
+    /// with their modification time. Only the file system is queried:
+    /// the notes are not read
+    pub fn most_recently_modified(&self, n: usize) -> Result<Vec<(PathBuf, SystemTime)>> {
+        let mut res = vec![];
+        for relative_path in self.note_paths()? {
+            let full_path = self.base_path.join(&relative_path);
+            let mtime = std::fs::metadata(&full_path)
+                .and_then(|m| m.modified())
+                .map_err(|source| Io {
+                    message: format!("While reading mtime of {full_path:?}"),
+                    source,
+                })?;
+            res.push((relative_path, mtime));
+        }
+        res.sort_by(|(p1, t1), (p2, t2)| t2.cmp(t1).then(p1.cmp(p2)));
+        res.truncate(n);
+        Ok(res)
+    }
+
+    /// Relative paths of the attachments of the note with the given id,
+    /// sorted: the files in the same `<year>` directory whose name starts
+    /// with the id, and whose extension is not a note extension
+    pub fn attachments_for(&self, id: &Id) -> Result<Vec<PathBuf>> {
+        let year_path = self.base_path.join(id.year());
+        if !year_path.is_dir() {
+            return Ok(vec![]);
+        }
+        let entries = std::fs::read_dir(&year_path).map_err(|source| Io {
+            message: format!("While reading {year_path:?}"),
+            source,
+        })?;
+        let mut res = vec![];
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let extension = path.extension().unwrap_or_default().to_string_lossy();
+            if path.is_file()
+                && name.starts_with(id.as_str())
+                && extension != BACKUP_EXTENSION
+                && !self.config.is_note_extension(&extension)
+            {
+                res.push(Path::new(id.year()).join(name));
+            }
+        }
+        res.sort();
+        Ok(res)
+    }
+
+    /// Load the note with the given id, if any.
+    /// Only the `<year>` directory matching the id is read.
+    /// Several notes with the same id is an error
+    pub fn load_by_id(&self, id: &Id) -> Result<Option<Note>> {
+        let year = id.year();
+        let mut matches = self
+            .note_names_in_year(year)?
+            .into_iter()
+            .filter(|n| n.starts_with(id.as_str()));
+        let name = match (matches.next(), matches.next()) {
+            (None, _) => return Ok(None),
+            (Some(name), None) => name,
+            (Some(_), Some(_)) => {
+                return Err(ParseError(format!(
+                    "Several notes found with id {}",
+                    id.as_str()
+                )))
+            }
+        };
+        self.load(&Path::new(year).join(name)).map(Some)
+    }
+
+    /// Same as `update`, but find the note from its id
+    pub fn update_by_id(&self, id: &Id) -> Result<PathBuf> {
+        let relative_path = self
+            .find_path_by_id(id)?
+            .ok_or_else(|| OSError(format!("No note found with id {}", id.as_str())))?;
+        self.update(&relative_path)
+    }
+
+    /// Write all the notes to `dest`, keeping their relative paths, for
+    /// tools that do not know about denote.
+    /// With `strip_front_matter`, only the text of the notes is written.
+    /// Otherwise, the front matter only contains the title, the date, and
+    /// the keywords as a `tags` list, like Hugo or Obsidian expect.
+    /// Create `dest` when needed
+    /// Return the number of exported notes
+    pub fn export_dir(&self, dest: &Path, strip_front_matter: bool) -> Result<usize> {
+        #[derive(Serialize)]
+        struct ExportedFrontMatter<'a> {
+            title: Option<&'a str>,
+            date: String,
+            tags: &'a [String],
+        }
+
+        let note_paths = self.note_paths()?;
+        for relative_path in &note_paths {
+            let note = self.load(relative_path)?;
+            let contents = if strip_front_matter {
+                note.text.clone()
+            } else {
+                let front_matter = ExportedFrontMatter {
+                    title: note.metadata.title(),
+                    date: note
+                        .metadata
+                        .front_matter_with_format(self.config.date_format)
+                        .date,
+                    tags: note.metadata.keywords(),
+                };
+                // Note: serde_yaml writes a leading `---`
+                let mut res = serde_yaml::to_string(&front_matter)
+                    .expect("front matter should always be serializable");
+                res.push_str("---\n");
+                res.push_str(&note.text);
+                res
+            };
+            let dest_path = dest.join(relative_path);
+            let parent_path = dest_path.parent().expect("dest path should have a parent");
+            std::fs::create_dir_all(parent_path).map_err(|source| Io {
+                message: format!("While creating {parent_path:?}"),
+                source,
+            })?;
+            std::fs::write(&dest_path, contents).map_err(|source| Io {
+                message: format!("While exporting note to {dest_path:?}"),
+                source,
+            })?;
+        }
+        Ok(note_paths.len())
+    }
+
+    /// Load the note with the given id, apply `edit` to it, then save it,
+    /// renaming it if its title or keywords changed. The id never changes
+    /// Return the new full path of the note
+    pub fn edit(&self, id: &Id, edit: impl FnOnce(&mut Note)) -> Result<PathBuf> {
+        let relative_path = self
+            .find_path_by_id(id)?
+            .ok_or_else(|| OSError(format!("No note found with id {}", id.as_str())))?;
+        let mut note = self.load(&relative_path)?;
+        edit(&mut note);
+        self.write_and_rename(&relative_path, &note)
+    }
+
+    /// Change the title of the note with the given id, and thus its slug.
+    /// The keywords are kept
+    /// Return the new full path of the note
+    pub fn retitle(&self, id: &Id, new_title: &str) -> Result<PathBuf> {
+        self.edit(id, |note| {
+            note.metadata.title = Some(new_title.to_owned());
+            note.metadata.slug = slug::slugify(new_title);
+        })
+    }
+
+    /// Copy the note at `relative_path` as specified by `backup`, replacing
+    /// any previous backup
+    fn backup_note(&self, relative_path: &Path, backup: &Backup) -> Result<()> {
+        let src = self.base_path.join(relative_path);
+        let dest_path = match backup {
+            Backup::Suffix => {
+                let mut name = src.clone().into_os_string();
+                name.push(format!(".{BACKUP_EXTENSION}"));
+                PathBuf::from(name)
+            }
+            Backup::Dir(dir) => dir.join(relative_path),
+        };
+        if self.dry_run {
+            self.plan(PlannedAction::Copy {
+                from: src,
+                to: dest_path,
+            });
+            return Ok(());
+        }
+        let parent_path = dest_path.parent().expect("dest path should have a parent");
+        std::fs::create_dir_all(parent_path).map_err(|source| Io {
+            message: format!("While creating {parent_path:?}"),
+            source,
+        })?;
+        std::fs::copy(&src, &dest_path).map_err(|source| Io {
+            message: format!("While copying {src:?} to {dest_path:?}"),
+            source,
+        })?;
+        Ok(())
+    }
+
+    /// Copy all the notes to `dest`, keeping the `<year>` directories
+    /// Create `dest` when needed
+    /// Return the number of copied files
+    pub fn backup_to(&self, dest: &Path) -> Result<usize> {
+        let note_paths = self.note_paths()?;
+        if self.dry_run {
+            for relative_path in &note_paths {
+                self.plan(PlannedAction::Copy {
+                    from: self.base_path.join(relative_path),
+                    to: dest.join(relative_path),
+                });
+            }
+            return Ok(note_paths.len());
+        }
+        std::fs::create_dir_all(dest).map_err(|source| Io {
+            message: format!("While creating backup directory {dest:?}"),
+            source,
+        })?;
+        for relative_path in &note_paths {
+            let src = self.base_path.join(relative_path);
+            let dest_path = dest.join(relative_path);
+            let parent_path = dest_path.parent().expect("dest path should have a parent");
+            std::fs::create_dir_all(parent_path).map_err(|source| Io {
+                message: format!("While creating {parent_path:?}"),
+                source,
+            })?;
+            std::fs::copy(&src, &dest_path).map_err(|source| Io {
+                message: format!("While copying {src:?} to {dest_path:?}"),
+                source,
+            })?;
+        }
+        Ok(note_paths.len())
+    }
+
+    /// Write all the notes at the given relative paths, or none of them.
+    /// Every note is first written to a temporary file in its destination
+    /// directory, and the temporary files are only renamed once all of them
+    /// have been written. If a rename fails, the remaining temporary files
+    /// are removed, but the notes already renamed are kept.
+    /// Renames are only atomic on the same filesystem, which is always the
+    /// case for the temporary files, but nothing is guaranteed across devices
+    pub fn save_all(&self, notes: &[(PathBuf, Note)]) -> Result<()> {
+        if self.dry_run {
+            for (relative_path, _) in notes {
+                self.plan(PlannedAction::Write(relative_path.clone()));
+            }
+            return Ok(());
+        }
+        let mut temp_files = vec![];
+        for (relative_path, note) in notes {
+            let full_path = self.base_path.join(relative_path);
+            let parent_path = full_path.parent().expect("full path should have a parent");
+            std::fs::create_dir_all(parent_path).map_err(|source| Io {
+                message: format!("While creating {parent_path:?}"),
+                source,
+            })?;
+            let to_write = note.dump_with_format(self.config.date_format);
+            // Note: on error, the temporary files written so far are
+            // removed when `temp_files` is dropped
+            let temp_file = write_temp_file(&full_path, &to_write)?;
+            temp_files.push((temp_file, relative_path));
+        }
+        for (temp_file, relative_path) in temp_files {
+            persist(temp_file, &self.base_path.join(relative_path))?;
+            if let Some(index) = &self.index {
+                index.replace(&self.base_path, None, relative_path, &self.config)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Save a note in the repository
+    /// Create `<year>` directory when needed
+    /// Return the path of the note relative to the base path, as
+    /// expected by `load` and `update` - see `save_full` for the full path
+    pub fn save(&self, note: &Note) -> Result<PathBuf> {
+        self.config.check_extension(note.metadata.extension())?;
+        let relative_path = &note.relative_path();
+        let full_path = &self.base_path.join(relative_path);
+
+        let parent_path = full_path.parent().expect("full path should have a parent");
+
+        if parent_path.exists() {
+            if parent_path.is_file() {
+                return Err(OSError(format!(
+                    "Cannot use {parent_path:?} as year path because there's a file here)"
+                )));
+            }
+        } else if !self.dry_run {
+            println!("Creating {parent_path:?}");
+            std::fs::create_dir_all(parent_path).map_err(|source| Io {
+                message: format!("While creating parent path {parent_path:?} for note"),
+                source,
+            })?;
+        }
+
+        self.write_note(relative_path, note)?;
+        Ok(relative_path.to_path_buf())
+    }
+
+    /// Same as `save`, but return the full path of the note
+    pub fn save_full(&self, note: &Note) -> Result<PathBuf> {
+        let relative_path = self.save(note)?;
+        Ok(self.base_path.join(relative_path))
+    }
+
+    /// Write the note at `relative_path`, even if it does not match
+    /// the metadata of the note - call `update` afterwards to fix the name
+    pub(crate) fn write_note(&self, relative_path: &Path, note: &Note) -> Result<()> {
+        let full_path = &self.base_path.join(relative_path);
+        if self.dry_run {
+            self.plan(PlannedAction::Write(relative_path.to_path_buf()));
+            return Ok(());
+        }
+        let to_write = note.dump_with_format(self.config.date_format);
+        write_atomically(full_path, &to_write)?;
+        if let Some(index) = &self.index {
+            index.replace(&self.base_path, None, relative_path, &self.config)?;
+        }
+        Ok(())
+    }
+
+    /// Number of notes in the repository.
+    /// Only the filenames are read, and no metadata is built
+    pub fn count(&self) -> Result<usize> {
+        if let Some(index) = &self.index {
+            return index.count();
+        }
+        let mut res = 0;
+        for year in self.years()? {
+            res += self.note_names_in_year(&year)?.len();
+        }
+        Ok(res)
+    }
+
+    /// Compute a summary of the repository.
+    /// Counting words requires loading every note - with `fast`, only the
+    /// filenames and the file sizes are read, and `total_words` is 0
+    pub fn statistics(&self, fast: bool) -> Result<RepoStats> {
+        let note_paths = self.note_paths()?;
+        let mut stats = RepoStats {
+            total_notes: note_paths.len(),
+            ..Default::default()
+        };
+        let mut keyword_counts: BTreeMap<String, usize> = BTreeMap::new();
+        for relative_path in &note_paths {
+            let full_path = self.base_path.join(relative_path);
+            let file_metadata = std::fs::metadata(&full_path).map_err(|source| Io {
+                message: format!("While reading metadata of {full_path:?}"),
+                source,
+            })?;
+            stats.total_bytes += file_metadata.len();
+
+            let info = parse_file_name_with_config(relative_path, &self.config)?;
+            let year = info
+                .id()
+                .year()
+                .parse()
+                .expect("FILENAME_RE should only match digits in the year");
+            stats.years.push(year);
+            *stats.notes_per_year.entry(year).or_default() += 1;
+            for keyword in info.keywords().iter().filter(|k| !k.is_empty()) {
+                *keyword_counts.entry(keyword.clone()).or_default() += 1;
+            }
+
+            if !fast {
+                let note = self.load(relative_path)?;
+                stats.total_words += note.word_count();
+            }
+        }
+        stats.years.sort_unstable();
+        stats.years.dedup();
+        stats.unique_keywords = keyword_counts.len();
+        let mut top_keywords: Vec<_> = keyword_counts.into_iter().collect();
+        top_keywords.sort_by(|(k1, c1), (k2, c2)| c2.cmp(c1).then(k1.cmp(k2)));
+        stats.most_common_keyword = top_keywords.first().map(|(k, _)| k.clone());
+        stats.top_keywords = top_keywords;
+        Ok(stats)
+    }
+
+    /// Same as `statistics(false)`: every note is loaded to count the
+    /// words, so this is as slow as `load_all` on large repositories
+    pub fn stats(&self) -> Result<RepoStats> {
+        self.statistics(false)
+    }
+
+    /// Relative paths of the notes having the given keyword
+    pub fn find_by_keyword(&self, keyword: &str) -> Result<Vec<PathBuf>> {
+        if let Some(index) = &self.index {
+            return index.find_by_keyword(keyword);
+        }
+        self.filter_by_filename(|info| info.keywords().iter().any(|k| k == keyword))
+    }
+
+    /// Load all the notes, and return the ones matching `predicate`.
+    /// Every file is read - use `filter_by_filename` when the predicate
+    /// only needs the id, slug, keywords or extension
+    pub fn filter<F>(&self, predicate: F) -> Result<Vec<Note>>
+    where
+        F: Fn(&Note) -> bool,
+    {
+        let mut res = vec![];
+        for relative_path in self.note_paths()? {
+            let note = self.load(&relative_path)?;
+            if predicate(&note) {
+                res.push(note);
+            }
+        }
+        Ok(res)
+    }
+
+    /// Relative paths of the notes whose file name matches `predicate`.
+    /// Only the filenames are read
+    pub fn filter_by_filename<F>(&self, predicate: F) -> Result<Vec<PathBuf>>
+    where
+        F: Fn(&FileInfo) -> bool,
+    {
+        let mut res = vec![];
+        for relative_path in self.note_paths()? {
+            let info = parse_file_name_with_config(&relative_path, &self.config)?;
+            if predicate(&info) {
+                res.push(relative_path);
+            }
+        }
+        Ok(res)
+    }
+
+    /// Relative paths of the notes having at least one of the given keywords.
+    /// Only the filenames are read
+    pub fn filter_keywords_any(&self, keywords: &[&str]) -> Result<Vec<PathBuf>> {
+        self.filter_by_filename(|info| {
+            keywords
+                .iter()
+                .any(|k| info.keywords().iter().any(|x| x == k))
+        })
+    }
+
+    /// Relative paths of the notes having all of the given keywords.
+    /// Only the filenames are read
+    pub fn filter_keywords_all(&self, keywords: &[&str]) -> Result<Vec<PathBuf>> {
+        self.filter_by_filename(|info| {
+            keywords
+                .iter()
+                .all(|k| info.keywords().iter().any(|x| x == k))
+        })
+    }
+
+    /// Group the relative paths of the notes by keyword - a note with
+    /// several keywords appears in several groups.
+    /// Only the filenames are read
+    pub fn notes_by_keyword(&self) -> Result<HashMap<String, Vec<PathBuf>>> {
+        let mut res: HashMap<String, Vec<PathBuf>> = HashMap::new();
+        for relative_path in self.note_paths()? {
+            let info = parse_file_name_with_config(&relative_path, &self.config)?;
+            for keyword in info.keywords() {
+                res.entry(keyword.clone())
+                    .or_default()
+                    .push(relative_path.clone());
+            }
+        }
+        Ok(res)
+    }
+
+    /// Same as `notes_by_keyword`, but sorted by keyword
+    pub fn notes_by_keyword_sorted(&self) -> Result<BTreeMap<String, Vec<PathBuf>>> {
+        Ok(self.notes_by_keyword()?.into_iter().collect())
+    }
+
+    /// The notes created on the given day, sorted by id
+    pub fn notes_created_on(&self, year: u16, month: u8, day: u8) -> Result<Vec<Note>> {
+        let month = time::Month::try_from(month)
+            .map_err(|_| ParseError(format!("invalid month: {month}")))?;
+        let date = Date::from_calendar_date(year.into(), month, day)
+            .map_err(|e| ParseError(format!("invalid date: {e}")))?;
+        self.notes_created_between(date, date)
+    }
+
+    /// The notes created since the Monday of the current week, sorted by id
+    pub fn notes_created_this_week(&self) -> Result<Vec<Note>> {
+        let today = self.now().date();
+        let days_since_monday = today.weekday().number_days_from_monday();
+        let monday = today - time::Duration::days(days_since_monday.into());
+        self.notes_created_between(monday, monday + time::Duration::days(6))
+    }
+
+    /// The notes created since the first day of the current month, sorted by id
+    pub fn notes_created_this_month(&self) -> Result<Vec<Note>> {
+        let today = self.now().date();
+        let first = today.replace_day(1).expect("every month has a first day");
+        let last_day = time::util::days_in_year_month(today.year(), today.month());
+        let last = today
+            .replace_day(last_day)
+            .expect("days_in_year_month should be a valid day");
+        self.notes_created_between(first, last)
+    }
+
+    /// The notes created between the two dates, both included
+    fn notes_created_between(&self, first: Date, last: Date) -> Result<Vec<Note>> {
+        let start = Id::from_date(&first.midnight().assume_utc());
+        let end_of_day = last.with_hms(23, 59, 59).expect("23:59:59 is a valid time");
+        let end = Id::from_date(&end_of_day.assume_utc());
+        self.filter_by_date_range(&start, &end)?
+            .iter()
+            .map(|p| self.load(p))
+            .collect()
+    }
+
+    /// Relative paths of the notes whose id is between `start` and `end`
+    /// (inclusive), sorted by id.
+    /// Without an index, only the `<year>` directories in the range are read
+    pub fn filter_by_date_range(&self, start: &Id, end: &Id) -> Result<Vec<PathBuf>> {
+        if let Some(index) = &self.index {
+            return index.filter_by_date_range(start, end);
+        }
+        let mut res = vec![];
+        for year in self.years()? {
+            if year.as_str() < start.year() || year.as_str() > end.year() {
+                continue;
+            }
+            for relative_path in self.note_paths_in_year(&year)? {
+                let info = parse_file_name_with_config(&relative_path, &self.config)?;
+                if info.id() >= start && info.id() <= end {
+                    res.push((info.id().clone(), relative_path));
+                }
+            }
+        }
+        res.sort();
+        Ok(res.into_iter().map(|(_, p)| p).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_id_sorting() {
+        let id1 = Id::from_str("20220707T142708").unwrap();
+        let id2 = Id::from_str("2022070lT142709").unwrap();
+        let id3 = Id::from_str("2022070lT142709").unwrap();
+
+        assert_eq!(id2, id3);
+        assert!(id1 < id2)
+    }
+
+    fn make_note() -> Note {
+        let id = Id::from_str("20220707T142708").unwrap();
+        let slug = "this-is-a-title".to_owned();
+        let title = Some("This is a title".to_owned());
+        let keywords = vec!["k1".to_owned(), "k2".to_owned()];
+        let extension = "md".to_owned();
+        let metadata = Metadata {
+            id,
+            filename_slug: slug.clone(),
+            slug,
+            title,
+            keywords,
+            extension,
+            aliases: vec![],
+            original_date: None,
+        };
+
+        Note::new(metadata, "This is my note".to_owned())
+    }
+
+    fn make_note_with(id: &str, title: &str) -> Note {
+        NoteBuilder::new()
+            .id(Id::from_str(id).unwrap())
+            .title(title)
+            .build()
+            .unwrap()
+    }
+
+    fn temp_repository() -> (tempfile::TempDir, NotesRepository) {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("test-denotes")
+            .tempdir()
+            .unwrap();
+        let notes = NotesRepository::open(&temp_dir).unwrap();
+        (temp_dir, notes)
+    }
+
+    #[test]
+    fn test_building_a_note() {
+        let note = NoteBuilder::new()
+            .id(Id::from_str("20220707T142708").unwrap())
+            .title("This is a title")
+            .keyword("k1")
+            .keyword("k2")
+            .text("This is my note")
+            .build()
+            .unwrap();
+
+        assert_eq!(note, make_note());
+    }
+
+    #[test]
+    fn test_building_a_note_without_id() {
+        NoteBuilder::new()
+            .title("This is a title")
+            .build()
+            .unwrap_err();
+    }
+
+    #[test]
+    fn test_created_at_matches_id() {
+        let note = make_note();
+        let created_at = note.created_at().unwrap();
+        assert_eq!(created_at.year().to_string(), &note.id()[0..4]);
+        assert_eq!(
+            note.metadata().id.human_date(),
+            "2022-07-07 14:27:08".to_string()
+        );
+    }
+
+    #[test]
+    fn test_slugify_title_when_creating_metadata() {
+        let id = Id::from_str("20220707T142708").unwrap();
+        let title = "This is a title".to_owned();
+        let keywords = vec!["k1".to_owned(), "k2".to_owned()];
+        let extension = "md".to_owned();
+        let metadata = Metadata::new(id, title, keywords, extension);
+
+        assert_eq!(metadata.slug(), "this-is-a-title");
+    }
+
+    #[test]
+    fn test_parse_info_from_file_name() {
+        let name = "20220707T142708--this-is-a-title__k1_k2.md";
+
+        let file_info = parse_file_name(name).unwrap();
+
+        assert_eq!(file_info.id().as_str(), "20220707T142708");
+        assert_eq!(file_info.slug(), "this-is-a-title");
+        assert_eq!(file_info.title(), "This is a title");
+        assert_eq!(file_info.keywords(), &["k1", "k2"]);
+        assert_eq!(file_info.extension(), "md");
+    }
+
+    #[test]
+    fn test_parse_file_name_from_a_path() {
+        let name = "20220707T142708--this-is-a-title__k1_k2.md";
+        let expected = parse_file_name(name).unwrap();
+
+        for path in [
+            Path::new("2022").join(name),
+            Path::new("/home/notes/2022").join(name),
+        ] {
+            let file_info = parse_file_name(&path).unwrap();
+            assert_eq!(file_info.id(), expected.id());
+            assert_eq!(file_info.slug(), expected.slug());
+            assert_eq!(file_info.keywords(), expected.keywords());
+        }
+        assert!(matches!(parse_file_name(".."), Err(ParseError(_))));
+        assert!(matches!(parse_file_name("/"), Err(ParseError(_))));
+    }
+
+    #[test]
+    fn test_parse_upper_case_extension() {
+        let name = "20220707T142708--t__k1.MD";
+
+        let file_info = parse_file_name(name).unwrap();
+
+        assert_eq!(file_info.extension(), "md");
+    }
+
+    #[test]
+    fn test_content_hash_changes_with_the_text() {
+        let note = make_note();
+        let hash = note.content_hash();
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, make_note().content_hash());
+
+        let other = Note::new(note.metadata().clone(), "Other text".to_owned());
+        assert_ne!(hash, other.content_hash());
+    }
+
+    #[test]
+    fn test_file_info_into_metadata() {
+        let name = "20220707T142708--this-is-a-title__k1_k2.md";
+        let file_info = parse_file_name(name).unwrap();
+
+        let metadata = file_info.into_metadata(Some("This is a title".to_owned()));
+
+        assert_eq!(&metadata, make_note().metadata());
+    }
+
+    #[test]
+    fn test_file_info_into_metadata_from_file() {
+        let (temp_dir, notes) = temp_repository();
+        let relative_path = notes.save(&make_note()).unwrap();
+        let file_info = parse_file_name(&relative_path).unwrap();
+
+        let metadata = file_info
+            .into_metadata_from_file(&temp_dir.path().join(&relative_path))
+            .unwrap();
+
+        assert_eq!(&metadata, make_note().metadata());
+        let file_info = parse_file_name(&relative_path).unwrap();
+        assert_eq!(
+            &file_info.into_metadata_with_title("This is a title".to_owned()),
+            make_note().metadata()
+        );
+    }
+
+    #[test]
+    fn test_load_errors_contain_the_path_of_the_note() {
+        let (temp_dir, notes) = temp_repository();
+        let relative_path = notes.save(&make_note()).unwrap();
+        let full_path = temp_dir.path().join(&relative_path);
+        std::fs::write(&full_path, "no front matter").unwrap();
+
+        let load_error = notes.load(&relative_path).unwrap_err().to_string();
+        let metadata_error = notes.load_metadata(&relative_path).unwrap_err().to_string();
+
+        assert!(load_error.contains(&format!("{full_path:?}")));
+        assert_eq!(load_error, metadata_error);
+    }
+
+    #[test]
+    fn test_generate_suitable_file_path_for_note() {
+        let note = make_note();
+        assert_eq!(
+            note.relative_path(),
+            Path::new("2022").join("20220707T142708--this-is-a-title__k1_k2.md")
+        );
+    }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn test_relative_path_uses_slash_separator() {
+        assert_eq!(
+            make_note().relative_path().to_string_lossy(),
+            "2022/20220707T142708--this-is-a-title__k1_k2.md"
+        );
+    }
+
+    #[test]
+    #[cfg(target_os = "windows")]
+    fn test_relative_path_uses_backslash_separator() {
+        assert_eq!(
+            make_note().relative_path().to_string_lossy(),
+            "2022\\20220707T142708--this-is-a-title__k1_k2.md"
+        );
+    }
+
+    #[test]
+    fn test_error_when_trying_to_load_notes_from_a_file() {
+        NotesRepository::open("src/lib.rs").unwrap_err();
+    }
+
+    #[test]
+    fn test_open_or_create_creates_missing_directory() {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("test-denotes")
+            .tempdir()
+            .unwrap();
+        let base_path = temp_dir.path().join("notes").join("personal");
+
+        NotesRepository::open(&base_path).unwrap_err();
+        NotesRepository::open_or_create(&base_path).unwrap();
+
+        assert!(base_path.is_dir());
+    }
+
+    #[test]
+    fn test_saving_and_loading() {
+        let (_temp_dir, notes) = temp_repository();
+        let note = make_note();
+        notes.save(&note).unwrap();
+
+        let relative_path = &note.relative_path();
+        let saved = notes.load(relative_path).unwrap();
+        assert_eq!(note, saved);
+    }
+
+    #[test]
+    fn test_backup_keeps_year_directories() {
+        let (temp_dir, notes) = temp_repository();
+        let note = make_note();
+        let relative_path = notes.save(&note).unwrap();
+
+        let backup_dir = temp_dir.path().join("backup");
+        let copied = notes.backup_to(&backup_dir).unwrap();
+
+        assert_eq!(copied, 1);
+        assert!(backup_dir.join(&relative_path).is_file());
+    }
+
+    #[test]
+    fn test_queries_using_the_index() {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("test-denotes")
+            .tempdir()
+            .unwrap();
+        let notes = NotesRepository::open_indexed(&temp_dir).unwrap();
+        assert!(temp_dir.path().join("denote.db").is_file());
+        assert_eq!(notes.count().unwrap(), 0);
+
+        let note = make_note();
+        let relative_path = notes.save(&note).unwrap();
+
+        assert_eq!(notes.count().unwrap(), 1);
+        assert_eq!(
+            notes.find_by_keyword("k1").unwrap(),
+            vec![relative_path.clone()]
+        );
+        assert!(notes.find_by_keyword("k3").unwrap().is_empty());
+        let start = Id::from_str("20220101T000000").unwrap();
+        let end = Id::from_str("20221231T235959").unwrap();
+        assert_eq!(
+            notes.filter_by_date_range(&start, &end).unwrap(),
+            vec![relative_path]
+        );
+    }
+
+    #[test]
+    fn test_title_wins_over_edited_filename_slug() {
+        let (temp_dir, notes) = temp_repository();
+        let note = make_note();
+        let relative_path = notes.save(&note).unwrap();
+
+        let edited = Path::new("2022/20220707T142708--my-own-slug__k1_k2.md");
+        std::fs::rename(
+            temp_dir.path().join(&relative_path),
+            temp_dir.path().join(edited),
+        )
+        .unwrap();
+
+        let loaded = notes.load(edited).unwrap();
+        assert_eq!(loaded.metadata().filename_slug(), "my-own-slug");
+        assert_eq!(loaded.metadata().slug(), "this-is-a-title");
+
+        let updated = notes.update(edited).unwrap();
+        assert_eq!(updated, temp_dir.path().join(&relative_path));
+        assert_eq!(
+            notes.take_slug_mismatches(),
+            [SlugMismatch {
+                path: edited.to_path_buf(),
+                filename_slug: "my-own-slug".to_owned(),
+                slug: "this-is-a-title".to_owned(),
+            }]
+        );
+
+        let untitled = Metadata::new(
+            Id::from_str("20220708T101010").unwrap(),
+            None,
+            vec![],
+            "md".to_owned(),
+        );
+        let untitled_path = notes.save(&Note::new(untitled, String::new())).unwrap();
+        notes.update(&untitled_path).unwrap();
+        assert!(notes.take_slug_mismatches().is_empty());
+    }
+
+    #[test]
+    fn test_yaml_error_is_kept_as_source() {
+        let err = FrontMatter::parse("title: [unfinished").unwrap_err();
+        let source = std::error::Error::source(&err).unwrap();
+        assert!(source.is::<serde_yaml::Error>());
+    }
+
+    #[test]
+    fn test_emptiness_and_presence_predicates() {
+        let (_temp_dir, notes) = temp_repository();
+        let note = make_note();
+        let id = Id::from_str(note.id()).unwrap();
+        assert!(notes.is_empty().unwrap());
+        assert!(!notes.has_id(&id).unwrap());
+
+        let relative_path = notes.save(&note).unwrap();
+
+        assert!(!notes.is_empty().unwrap());
+        assert!(notes.has_note(&relative_path));
+        assert!(!notes.has_note(Path::new("2022/nope.md")));
+        assert!(notes.has_id(&id).unwrap());
+    }
+
+    #[test]
+    fn test_iterating_over_notes() {
+        let (temp_dir, notes) = temp_repository();
+        let note = make_note();
+        notes.save(&note).unwrap();
+        std::fs::write(
+            temp_dir.path().join("2022/20220708T000000--broken__k1.md"),
+            "no front matter",
+        )
+        .unwrap();
+
+        let all: Vec<_> = notes.iter().collect();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].as_ref().unwrap(), note.metadata());
+        assert!(all[1].is_err());
+
+        let first: Vec<_> = notes.iter().take(1).collect();
+        assert_eq!(first.len(), 1);
+    }
+
+    #[test]
+    fn test_validate_and_repair_filenames() {
+        let (temp_dir, notes) = temp_repository();
+        let note = make_note();
+        let relative_path = notes.save(&note).unwrap();
+        let misfiled = Path::new("2021/20220707T142708--this-is-a-title__k1_k2.md");
+        std::fs::create_dir(temp_dir.path().join("2021")).unwrap();
+        std::fs::rename(
+            temp_dir.path().join(&relative_path),
+            temp_dir.path().join(misfiled),
+        )
+        .unwrap();
+
+        let issues = notes.validate().unwrap();
+        assert_eq!(
+            issues,
+            vec![ValidationIssue {
+                path: misfiled.to_path_buf(),
+                kind: IssueKind::MisfiledYear,
+                description: "id 20220707T142708 should be in 2022".to_string(),
+                suggested_new_path: Some(relative_path.clone()),
+            }]
+        );
+
+        let renames = notes.repair_filenames().unwrap();
+        assert_eq!(renames, vec![(misfiled.to_path_buf(), relative_path)]);
+        assert!(notes.validate().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_update_by_id() {
+        let (temp_dir, notes) = temp_repository();
+        let note = make_note();
+        let relative_path = notes.save(&note).unwrap();
+        let full_path = temp_dir.path().join(&relative_path);
+        let contents = std::fs::read_to_string(&full_path).unwrap();
+        std::fs::write(&full_path, contents.replace("This is a title", "New title")).unwrap();
+
+        let id = Id::from_str(note.id()).unwrap();
+        let new_path = notes.update_by_id(&id).unwrap();
+
+        assert_eq!(
+            new_path,
+            temp_dir
+                .path()
+                .join("2022/20220707T142708--new-title__k1_k2.md")
+        );
+        let missing = Id::from_str("20220707T000000").unwrap();
+        notes.update_by_id(&missing).unwrap_err();
+    }
+
+    #[test]
+    fn test_update_with_backup() {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("test-denotes")
+            .tempdir()
+            .unwrap();
+        let backup_dir = temp_dir.path().join("backup");
+        for backup in [Backup::Suffix, Backup::Dir(backup_dir.clone())] {
+            let notes = NotesRepository::open(&temp_dir)
+                .unwrap()
+                .with_backup(backup.clone());
+            let relative_path = notes.save(&make_note()).unwrap();
+            let full_path = temp_dir.path().join(&relative_path);
+            let contents = std::fs::read_to_string(&full_path).unwrap();
+            let edited = contents.replace("This is a title", "New title");
+            std::fs::write(&full_path, &edited).unwrap();
+
+            let new_path = notes.update(&relative_path).unwrap();
+
+            let backup_path = match backup {
+                Backup::Suffix => temp_dir
+                    .path()
+                    .join("2022/20220707T142708--this-is-a-title__k1_k2.md.bak"),
+                Backup::Dir(_) => backup_dir.join(&relative_path),
+            };
+            assert_eq!(std::fs::read_to_string(&backup_path).unwrap(), edited);
+            assert!(!full_path.exists());
+            std::fs::remove_file(new_path).unwrap();
+        }
+        let notes = NotesRepository::open(&temp_dir).unwrap();
+        assert_eq!(notes.count().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_backups_are_not_notes_nor_attachments() {
+        let (temp_dir, notes) = temp_repository();
+        let notes = notes.with_backup(Backup::Suffix);
+        let relative_path = notes.save(&make_note()).unwrap();
+        let full_path = temp_dir.path().join(&relative_path);
+        let contents = std::fs::read_to_string(&full_path).unwrap();
+        std::fs::write(&full_path, contents.replace("This is a title", "New title")).unwrap();
+
+        notes.update(&relative_path).unwrap();
+
+        let backup_path = temp_dir
+            .path()
+            .join("2022/20220707T142708--this-is-a-title__k1_k2.md.bak");
+        assert!(backup_path.exists());
+        assert_eq!(notes.count().unwrap(), 1);
+        assert!(notes
+            .attachments_for(&make_note().metadata.id)
+            .unwrap()
+            .is_empty());
+        assert_eq!(cli::changed_note(temp_dir.path(), &backup_path), None);
+    }
+
+    #[test]
+    fn test_front_matter_date_formats() {
+        let metadata = make_note().metadata;
+        let expected_id = Id::from_str("20220707T142708").unwrap();
+        for (date_format, expected_date) in [
+            (DateFormat::HumanReadable, "2022-07-07 14:27:08"),
+            (DateFormat::Iso8601, "2022-07-07T14:27:08Z"),
+            (DateFormat::DateOnly, "2022-07-07"),
+        ] {
+            let front_matter = metadata.front_matter_with_format(date_format);
+            assert_eq!(front_matter.date, expected_date);
+            let id = front_matter.date_as_id().unwrap();
+            if date_format == DateFormat::DateOnly {
+                assert_eq!(id.as_str(), "20220707T000000");
+            } else {
+                assert_eq!(id, expected_id);
+            }
+        }
+    }
+
+    #[test]
+    fn test_dump_is_stable() {
+        let note = make_note();
+        let dumped = note.dump();
+        assert!(dumped.ends_with("---\nThis is my note\n"));
+
+        let id = Id::from_str(note.id()).unwrap();
+        let parsed = get_note_from_markdown(id, dumped.clone(), Default::default()).unwrap();
+        assert_eq!(parsed.dump(), dumped);
+    }
+
+    #[test]
+    fn test_notes_by_keyword() {
+        let (_temp_dir, notes) = temp_repository();
+        let first = notes.save(&make_note()).unwrap();
+        let other = NoteBuilder::new()
+            .id(Id::from_str("20220708T142708").unwrap())
+            .title("Other")
+            .keyword("k2")
+            .build()
+            .unwrap();
+        let second = notes.save(&other).unwrap();
+
+        let by_keyword = notes.notes_by_keyword_sorted().unwrap();
+
+        let keywords: Vec<_> = by_keyword.keys().collect();
+        assert_eq!(keywords, vec!["k1", "k2"]);
+        assert_eq!(by_keyword["k1"], vec![first.clone()]);
+        assert_eq!(by_keyword["k2"], vec![first, second]);
+    }
+
+    #[test]
+    fn test_parse_note_with_crlf_line_endings() {
+        let contents = "---\r\ntitle: This is a title\r\ndate: 2022-07-07 14:27:08\r\nkeywords: k1 k2\r\n---\r\nThis is my note\r\n";
+        let id = Id::from_str("20220707T142708").unwrap();
+
+        let note = get_note_from_markdown(id, contents.to_string(), Default::default()).unwrap();
+
+        assert_eq!(note.metadata().title(), Some("This is a title"));
+        assert_eq!(note.text, "This is my note\n");
+        assert!(!note.dump().contains('\r'));
+    }
+
+    #[test]
+    fn test_keyword_predicate_and_deduplication() {
+        let note = make_note();
+        assert!(note.has_keyword("k1"));
+        assert!(!note.has_keyword("k3"));
+
+        let mut front_matter = FrontMatter::parse(
+            "title: t\ndate: 2022-07-07 14:27:08\nkeywords: rust rust systems\n",
+        )
+        .unwrap();
+        front_matter.deduplicate_keywords();
+        assert_eq!(front_matter.keywords, ["rust", "systems"]);
+
+        let mut metadata = note.metadata().clone();
+        metadata.keywords = vec!["k1".to_owned(), "k2".to_owned(), "k1".to_owned()];
+        metadata.deduplicate_keywords();
+        assert_eq!(metadata.keywords(), &["k1", "k2"]);
+    }
+
+    #[test]
+    fn test_repair_misfiled_notes() {
+        let (temp_dir, notes) = temp_repository();
+        let misfiled = Path::new("2021/20220707T142708--whatever__k1.md");
+        std::fs::create_dir(temp_dir.path().join("2021")).unwrap();
+        std::fs::write(temp_dir.path().join(misfiled), make_note().dump()).unwrap();
+
+        let moves = notes.repair().unwrap();
+
+        let expected = PathBuf::from("2022/20220707T142708--whatever__k1.md");
+        assert_eq!(moves, vec![(misfiled.to_path_buf(), expected.clone())]);
+        assert!(temp_dir.path().join(expected).is_file());
+        assert!(notes.repair().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rename_keyword_globally_reports_progress() {
+        let calls = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let recorded = calls.clone();
+        let (_temp_dir, notes) = temp_repository();
+        let notes = notes.with_progress(Box::new(move |current, total| {
+            recorded.lock().unwrap().push((current, total))
+        }));
+        let old_path = notes.save(&make_note()).unwrap();
+
+        let new_paths = notes.rename_keyword_globally("k1", "rust").unwrap();
+
+        assert_eq!(
+            new_paths,
+            vec![PathBuf::from(
+                "2022/20220707T142708--this-is-a-title__rust_k2.md"
+            )]
+        );
+        assert!(!notes.has_note(&old_path));
+        assert_eq!(*calls.lock().unwrap(), vec![(1, 1)]);
+    }
+
+    #[test]
+    fn test_load_metadata_only() {
+        let (_temp_dir, notes) = temp_repository();
+        let note = make_note();
+        let relative_path = notes.save(&note).unwrap();
+
+        let metadata = notes.load_metadata(&relative_path).unwrap();
+
+        assert_eq!(&metadata, note.metadata());
+        assert_eq!(
+            notes.find_by_title("This is a title").unwrap(),
+            vec![metadata]
+        );
+        assert!(notes.find_by_title("Other").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_add_and_remove_keywords() {
+        let mut metadata = make_note().metadata;
+
+        metadata.add_keyword("a");
+        metadata.add_keyword("k1");
+        assert_eq!(metadata.keywords(), &["a", "k1", "k2"]);
+
+        metadata.remove_keyword("k1");
+        metadata.remove_keyword("nope");
+        assert_eq!(metadata.keywords(), &["a", "k2"]);
+    }
+
+    #[test]
+    fn test_tag_rename_removes_old_keyword() {
+        let (temp_dir, notes) = temp_repository();
+        let full_path = temp_dir.path().join(notes.save(&make_note()).unwrap());
+        cli::add_tag(temp_dir.path(), &full_path, "rust", false).unwrap();
+        let backup_dir = temp_dir.path().join("backup");
+
+        cli::rename_tag(temp_dir.path(), "rust", "systems", false, Some(&backup_dir)).unwrap();
+
+        assert!(notes.find_by_keyword("rust").unwrap().is_empty());
+        assert_eq!(notes.find_by_keyword("systems").unwrap().len(), 1);
+        let backup = NotesRepository::open(&backup_dir).unwrap();
+        assert_eq!(backup.find_by_keyword("rust").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_tag_keywords_are_sanitized() {
+        let (temp_dir, notes) = temp_repository();
+        let full_path = temp_dir.path().join(notes.save(&make_note()).unwrap());
+
+        let new_path = cli::add_tag(temp_dir.path(), &full_path, "Foo_Bar", false).unwrap();
+
+        assert_eq!(
+            new_path,
+            temp_dir
+                .path()
+                .join("2022/20220707T142708--this-is-a-title__foobar_k1_k2.md")
+        );
+        let new_path = cli::remove_tag(temp_dir.path(), &new_path, "FOOBAR", false).unwrap();
+        assert_eq!(new_path, full_path);
+
+        cli::rename_tag(temp_dir.path(), "K1", "Big Data", false, None).unwrap();
+        assert_eq!(notes.find_by_keyword("bigdata").unwrap().len(), 1);
+        assert!(notes.rename_keyword_globally("k2", "--").is_err());
+    }
+
+    #[test]
+    fn test_tag_rename_dry_run_plans_the_changes() {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("test-denotes")
+            .tempdir()
+            .unwrap();
+        let notes = NotesRepository::open(&temp_dir).unwrap().with_dry_run(true);
+        let relative_path = make_note().relative_path();
+        NotesRepository::open(&temp_dir)
+            .unwrap()
+            .save(&make_note())
+            .unwrap();
+        let backup_dir = temp_dir.path().join("backup");
+
+        assert_eq!(notes.backup_to(&backup_dir).unwrap(), 1);
+        notes.rename_keyword_globally("k1", "rust").unwrap();
+
+        assert_eq!(
+            notes.take_planned_actions(),
+            [
+                PlannedAction::Copy {
+                    from: temp_dir.path().join(&relative_path),
+                    to: backup_dir.join(&relative_path),
+                },
+                PlannedAction::Write(
+                    Path::new("2022").join("20220707T142708--this-is-a-title__rust_k2.md")
+                ),
+                PlannedAction::Delete(relative_path.clone()),
+            ]
+        );
+        assert!(!backup_dir.exists());
+        assert!(temp_dir.path().join(relative_path).exists());
+    }
+
+    #[test]
+    fn test_find_by_slug() {
+        let (_temp_dir, notes) = temp_repository();
+        let note = make_note();
+        notes.save(&note).unwrap();
+
+        assert_eq!(notes.find_by_slug("this-is-a-title").unwrap(), Some(note));
+        assert_eq!(notes.find_by_slug("other").unwrap(), None);
+    }
+
+    #[test]
+    fn test_find_all_by_slug_with_colliding_slugs() {
+        let (_temp_dir, notes) = temp_repository();
+        let first = make_note();
+        let second = make_note_with("20220708T101010", "This is a title");
+        notes.save(&second).unwrap();
+        notes.save(&first).unwrap();
+
+        let found = notes.find_all_by_slug("this-is-a-title").unwrap();
+
+        assert_eq!(found, vec![first.clone(), second]);
+        assert_eq!(notes.find_by_slug("this-is-a-title").unwrap(), Some(first));
+    }
+
+    #[test]
+    fn test_load_absolute() {
+        let (temp_dir, notes) = temp_repository();
+        let note = make_note();
+        let full_path = temp_dir.path().join(notes.save(&note).unwrap());
+
+        assert_eq!(notes.load_absolute(&full_path).unwrap(), note);
+
+        let outside = temp_dir.path().join("..").join("2022").join("note.md");
+        assert!(notes.load_absolute(&outside).is_err());
+        assert!(notes.load_absolute(Path::new("/etc/passwd")).is_err());
+    }
+
+    #[test]
+    fn test_edit_requires_exactly_one_match() {
+        let (temp_dir, notes) = temp_repository();
+        notes.save(&make_note()).unwrap();
+        let other = make_note_with("20220708T101010", "This is a title");
+        notes.save(&other).unwrap();
+
+        let err = cli::edit(temp_dir.path(), "nope", None, false).unwrap_err();
+        assert!(err.to_string().contains("No note matching"));
+        let err = cli::edit(temp_dir.path(), "this-is-a-title", None, false).unwrap_err();
+        assert!(err.to_string().contains("Several notes matching"));
+        let err = cli::edit(temp_dir.path(), "20220708", Some("false"), false).unwrap_err();
+        assert!(err.to_string().contains("editor did not exit"));
+        cli::edit(temp_dir.path(), "20220708", Some("true"), false).unwrap();
+    }
+
+    #[test]
+    fn test_render_note() {
+        let (temp_dir, notes) = temp_repository();
+        notes.save(&make_note()).unwrap();
+        let base_path = temp_dir.path();
+
+        let full = cli::render_note(base_path, "20220707T142708", cli::ShowMode::Full).unwrap();
+        assert_eq!(full, make_note().dump());
+        let body = cli::render_note(base_path, "this-is-a-title", cli::ShowMode::BodyOnly).unwrap();
+        assert_eq!(body, "This is my note\n");
+        let json = cli::render_note(base_path, "20220707", cli::ShowMode::Metadata).unwrap();
+        let metadata: Metadata = serde_json::from_str(&json).unwrap();
+        assert_eq!(&metadata, make_note().metadata());
+        let err = cli::render_note(base_path, "nope", cli::ShowMode::Full).unwrap_err();
+        assert!(err.to_string().contains("No note matching nope"));
+    }
+
+    #[test]
+    fn test_count_skips_unrelated_files() {
+        let (temp_dir, notes) = temp_repository();
+        notes.save(&make_note()).unwrap();
+        std::fs::write(temp_dir.path().join("2022").join("README.txt"), "").unwrap();
+
+        assert_eq!(notes.count().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_id_to_rfc3339() {
+        let id = Id::from_str("20220707T142708").unwrap();
+        let formatted = id.to_rfc3339();
+
+        assert_eq!(formatted, "2022-07-07T14:27:08Z");
+        assert_eq!(
+            OffsetDateTime::parse(&formatted, &Rfc3339).unwrap(),
+            id.to_offset_date_time().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_id_date_parts() {
+        let id = Id::from_str("20220707T142708").unwrap();
+
+        assert_eq!((id.year(), id.month(), id.day()), ("2022", "07", "07"));
+        assert!(Id::from_str("2022070éT142708").is_err());
+    }
+
+    #[test]
+    fn test_extract_links() {
+        let text = r#"See [[20220707T142708|the first note]] and [[other-note]],
+[the docs](https://example.com/docs "Docs") or <https://example.com>
+"#;
+        let note = Note::new(make_note().metadata, text.to_owned());
+
+        assert_eq!(
+            note.extract_links(),
+            vec![
+                NoteLink::Wikilink {
+                    target: "20220707T142708".to_owned()
+                },
+                NoteLink::Wikilink {
+                    target: "other-note".to_owned()
+                },
+                NoteLink::MarkdownLink {
+                    text: "the docs".to_owned(),
+                    url: "https://example.com/docs".to_owned()
+                },
+                NoteLink::Autolink {
+                    url: "https://example.com".to_owned()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_link_graph() {
+        let (_temp_dir, notes) = temp_repository();
+        let note = Note::new(make_note().metadata, "[[other]]".to_owned());
+        notes.save(&note).unwrap();
+
+        let graph = notes.build_link_graph().unwrap();
+
+        let id = Id::from_str("20220707T142708").unwrap();
+        assert_eq!(
+            graph[&id],
+            vec![NoteLink::Wikilink {
+                target: "other".to_owned()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_export_combined() {
+        let (_temp_dir, notes) = temp_repository();
+        let other = NoteBuilder::new()
+            .id(Id::from_str("20220708T101010").unwrap())
+            .title("Other")
+            .text("Other text")
+            .build()
+            .unwrap();
+        notes.save(&other).unwrap();
+        notes.save(&make_note()).unwrap();
+
+        let ids = [
+            Id::from_str("20220708T101010").unwrap(),
+            Id::from_str("20220707T142708").unwrap(),
+        ];
+        let combined = notes.export_combined(&ids).unwrap();
+
+        assert_eq!(
+            combined,
+            "# This is a title\n\n---\n\nThis is my note\n\n# Other\n\n---\n\nOther text\n"
+        );
+        let missing = Id::from_str("20220709T101010").unwrap();
+        let err = notes.export_combined(&[missing]).unwrap_err();
+        assert!(err.to_string().contains("20220709T101010"));
+    }
+
+    #[test]
+    fn test_backlinks() {
+        let (_temp_dir, notes) = temp_repository();
+        // first <- second (by id), first <- third (by slug), second <- third (by url)
+        let first = make_note();
+        let second = NoteBuilder::new()
+            .id(Id::from_str("20220708T101010").unwrap())
+            .title("Second")
+            .text("See [[20220707T142708]]")
+            .build()
+            .unwrap();
+        let third = NoteBuilder::new()
+            .id(Id::from_str("20220709T101010").unwrap())
+            .title("Third")
+            .text("See [[this-is-a-title]] and [second](20220708T101010--second__.md)")
+            .build()
+            .unwrap();
+        notes.save(&first).unwrap();
+        let second_path = notes.save(&second).unwrap();
+        let third_path = notes.save(&third).unwrap();
+
+        let first_id = Id::from_str("20220707T142708").unwrap();
+        assert_eq!(
+            notes.backlinks_for(&first_id).unwrap(),
+            vec![second_path.clone(), third_path.clone()]
+        );
+
+        let map = notes.build_backlink_map().unwrap();
+        assert_eq!(map[&first_id], vec![second_path, third_path.clone()]);
+        assert_eq!(
+            map[&Id::from_str("20220708T101010").unwrap()],
+            vec![third_path]
+        );
+        assert!(map[&Id::from_str("20220709T101010").unwrap()].is_empty());
+        assert!(serde_json::to_string(&map).is_ok());
+    }
+
+    #[test]
+    fn test_import_dir_uses_distinct_ids() {
+        let import_dir = tempfile::Builder::new()
+            .prefix("test-import")
+            .tempdir()
+            .unwrap();
+        for title in ["one", "two", "three"] {
+            let contents = format!("---\ntitle: {title}\ndate: 2022-07-07\nkeywords: k1\n---\n");
+            std::fs::write(import_dir.path().join(format!("{title}.md")), contents).unwrap();
+        }
+        std::fs::write(import_dir.path().join("ignored.txt"), "").unwrap();
+        let now = Id::from_str("20220707T142708")
+            .unwrap()
+            .to_offset_date_time()
+            .unwrap();
+        let (_temp_dir, notes) = temp_repository();
+        let notes = notes.with_clock(Arc::new(FixedClock(now)));
+
+        let saved = notes.import_dir(import_dir.path()).unwrap();
+
+        assert_eq!(
+            saved,
+            vec![
+                Path::new("2022").join("20220707T142708--one__k1.md"),
+                Path::new("2022").join("20220707T142709--three__k1.md"),
+                Path::new("2022").join("20220707T142710--two__k1.md"),
+            ]
+        );
+        assert_eq!(notes.count().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_parse_front_matter_borrows_the_text() {
+        let contents = "---\ntitle: This is a title\ndate: 2022-07-07 14:27:08\nkeywords: k1 k2\n---\nThis is my note\n";
+
+        let (front_matter, text) = parse_front_matter(contents).unwrap();
+
+        assert_eq!(front_matter.title(), Some("This is a title"));
+        assert_eq!(text, "This is my note\n");
+        assert!(std::ptr::eq(text, &contents[contents.len() - text.len()..]));
+    }
+
+    #[test]
+    fn test_parse_front_matter_edge_cases() {
+        let missing_end = "---\ntitle: This is a title\ndate: 2022-07-07\nkeywords: k1\n";
+        let err = parse_front_matter(missing_end).unwrap_err();
+        assert_eq!(err.to_string(), "parse error: Unfinished front matter");
+
+        let empty = "---\n---\nThis is my note\n";
+        assert!(parse_front_matter(empty).is_err());
+
+        let no_front_matter = "This is my note\n";
+        let err = parse_front_matter(no_front_matter).unwrap_err();
+        assert_eq!(err.to_string(), "parse error: Missing front matter");
+
+        let err = parse_front_matter("").unwrap_err();
+        assert_eq!(err.to_string(), "parse error: Missing front matter");
+    }
+
+    #[test]
+    fn test_imports_in_the_same_second_get_distinct_ids() {
+        let (temp_dir, notes) = temp_repository();
+        notes.save(&make_note()).unwrap();
+        let now = Id::from_str("20220707T142708")
+            .unwrap()
+            .to_offset_date_time()
+            .unwrap();
+
+        let id = notes.next_free_id(now).unwrap();
+
+        assert_eq!(id.as_str(), "20220707T142709");
+
+        let markdown_path = temp_dir.path().join("note.md");
+        let contents = "---\ntitle: This is a title\ndate: 2022-07-07\nkeywords: k1\n---\n";
+        std::fs::write(&markdown_path, contents).unwrap();
+        let first = notes.import_from_markdown(&markdown_path).unwrap();
+        let second = notes.import_from_markdown(&markdown_path).unwrap();
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn test_statistics() {
+        let (temp_dir, notes) = temp_repository();
+        let first_path = notes.save(&make_note()).unwrap();
+        let other = NoteBuilder::new()
+            .id(Id::from_str("20230101T101010").unwrap())
+            .title("Other")
+            .keyword("k2")
+            .keyword("k3")
+            .text("one two three")
+            .build()
+            .unwrap();
+        let other_path = notes.save(&other).unwrap();
+        let total_bytes = [first_path, other_path]
+            .iter()
+            .map(|p| std::fs::metadata(temp_dir.path().join(p)).unwrap().len())
+            .sum();
+
+        let stats = notes.statistics(false).unwrap();
+
+        assert_eq!(
+            stats,
+            RepoStats {
+                total_notes: 2,
+                total_words: 7,
+                total_bytes,
+                years: vec![2022, 2023],
+                notes_per_year: BTreeMap::from([(2022, 1), (2023, 1)]),
+                unique_keywords: 3,
+                top_keywords: vec![
+                    ("k2".to_owned(), 2),
+                    ("k1".to_owned(), 1),
+                    ("k3".to_owned(), 1)
+                ],
+                most_common_keyword: Some("k2".to_owned()),
+            }
+        );
+        assert_eq!(notes.stats().unwrap(), stats);
+        let fast_stats = notes.statistics(true).unwrap();
+        assert_eq!(fast_stats.total_words, 0);
+        assert_eq!(fast_stats.total_bytes, total_bytes);
+    }
+
+    #[test]
+    fn test_aliases() {
+        let (temp_dir, notes) = temp_repository();
+        let year_path = temp_dir.path().join("2022");
+        std::fs::create_dir(&year_path).unwrap();
+        let contents = "---\ntitle: This is a title\ndate: 2022-07-07 14:27:08\nkeywords: k1 k2\naliases:\n- first\n- premier\n---\nThis is my note\n";
+        std::fs::write(
+            year_path.join("20220707T142708--this-is-a-title__k1_k2.md"),
+            contents,
+        )
+        .unwrap();
+
+        let metadata = notes.get_by_alias("premier").unwrap().unwrap();
+        assert_eq!(metadata.aliases(), &["first", "premier"]);
+        assert!(notes.get_by_alias("nope").unwrap().is_none());
+
+        let (front_matter, _) = parse_front_matter(
+            "---\ntitle: t\ndate: 2022-07-07\nkeywords: k1\naliases: a b\n---\n",
+        )
+        .unwrap();
+        assert_eq!(front_matter.aliases(), &["a", "b"]);
+
+        let note = notes
+            .load(Path::new("2022/20220707T142708--this-is-a-title__k1_k2.md"))
+            .unwrap();
+        let dumped = note.dump();
+        let (front_matter, _) = parse_front_matter(&dumped).unwrap();
+        assert_eq!(front_matter.aliases(), &["first", "premier"]);
+        assert!(!make_note().dump().contains("aliases"));
+    }
+
+    #[test]
+    fn test_delete_prunes_empty_year_directories() {
+        let (temp_dir, notes) = temp_repository();
+        std::fs::create_dir(temp_dir.path().join("2021")).unwrap();
+        std::fs::create_dir(temp_dir.path().join("2020")).unwrap();
+        std::fs::write(temp_dir.path().join("2020").join("README"), "").unwrap();
+        let relative_path = notes.save(&make_note()).unwrap();
+
+        notes.delete(&relative_path).unwrap();
+
+        assert!(!temp_dir.path().join("2022").exists());
+        assert!(!temp_dir.path().join("2021").exists());
+        assert!(temp_dir.path().join("2020").exists());
+        assert!(notes.prune_empty_year_directories().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_drafts() {
+        let mut note = NoteBuilder::new()
+            .id(Id::from_str("20220707T142708").unwrap())
+            .title("This is a title")
+            .keyword("draft")
+            .keyword("k1")
+            .build()
+            .unwrap();
+        assert!(note.is_draft());
+
+        note.promote();
+
+        assert!(!note.is_draft());
+        assert_eq!(note.metadata().keywords(), &["k1"]);
+        assert!(!make_note().is_draft());
+    }
+
+    #[test]
+    fn test_validate_drafts_directory() {
+        let (temp_dir, notes) = temp_repository();
+        let draft_path = temp_dir.path().join("draft");
+        std::fs::create_dir(&draft_path).unwrap();
+        let name = "20220707T142708--this-is-a-title__k1_k2.md";
+        std::fs::write(draft_path.join(name), make_note().dump()).unwrap();
+
+        let issues = notes.validate().unwrap();
+
+        assert_eq!(
+            issues,
+            vec![ValidationIssue::new(
+                Path::new("draft").join(name),
+                IssueKind::MissingDraftKeyword,
+                "notes in draft/ should have the draft keyword",
+            )]
+        );
+    }
+
+    #[test]
+    fn test_dump_preserves_date_with_offset() {
+        let (temp_dir, notes) = temp_repository();
+        let year_path = temp_dir.path().join("2022");
+        std::fs::create_dir(&year_path).unwrap();
+        let contents = "---\ntitle: This is a title\ndate: 2022-07-07T14:27:08+02:00\nkeywords: k1 k2\n---\nThis is my note\n";
+        let relative_path = Path::new("2022").join("20220707T142708--this-is-a-title__k1_k2.md");
+        std::fs::write(temp_dir.path().join(&relative_path), contents).unwrap();
+
+        let note = notes.load(&relative_path).unwrap();
+        notes.save(&note).unwrap();
+        let reloaded = notes.load(&relative_path).unwrap();
+
+        assert_eq!(reloaded.front_matter().date, "2022-07-07T14:27:08+02:00");
+        assert_eq!(reloaded, note);
+    }
+
+    #[test]
+    fn test_dry_run_does_not_touch_the_disk() {
+        let (temp_dir, notes) = temp_repository();
+        let relative_path = notes.save(&make_note()).unwrap();
+        let notes = notes.with_dry_run(true);
+
+        let other = make_note_with("20230101T101010", "Other");
+        notes.save(&other).unwrap();
+        let renamed = notes.rename_keyword_globally("k1", "k3").unwrap();
+
+        assert_eq!(
+            renamed,
+            vec![Path::new("2022").join("20220707T142708--this-is-a-title__k3_k2.md")]
+        );
+        assert!(!temp_dir.path().join("2023").exists());
+        assert_eq!(notes.note_paths().unwrap(), vec![relative_path.clone()]);
+        assert_eq!(
+            notes.take_planned_actions(),
+            vec![
+                PlannedAction::Write(other.relative_path()),
+                PlannedAction::Write(renamed[0].clone()),
+                PlannedAction::Delete(relative_path),
+            ]
+        );
+        assert!(notes.take_planned_actions().is_empty());
+    }
+
+    #[test]
+    fn test_watch_only_updates_notes() {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("test-denotes")
+            .tempdir()
+            .unwrap();
+        let notes = NotesRepository::open_indexed(&temp_dir).unwrap();
+        let relative_path = notes.save(&make_note()).unwrap();
+        let base_path = temp_dir.path();
+
+        assert_eq!(
+            cli::changed_note(base_path, &base_path.join(&relative_path)),
+            Some(relative_path)
+        );
+        assert_eq!(
+            cli::changed_note(base_path, &base_path.join(index::INDEX_FILE_NAME)),
+            None
+        );
+        assert_eq!(cli::changed_note(base_path, &base_path.join("2022")), None);
+    }
+
+    #[test]
+    fn test_save_all_writes_nothing_on_failure() {
+        let (temp_dir, notes) = temp_repository();
+        let note = make_note();
+        let other = make_note_with("20230101T101010", "Other");
+        // A file where the `2023` directory should be makes the second write fail
+        std::fs::write(temp_dir.path().join("2023"), "").unwrap();
+
+        let batch = vec![
+            (note.relative_path(), note.clone()),
+            (other.relative_path(), other.clone()),
+        ];
+        assert!(notes.save_all(&batch).is_err());
+
+        let year_path = temp_dir.path().join("2022");
+        assert_eq!(std::fs::read_dir(&year_path).unwrap().count(), 0);
+
+        std::fs::remove_file(temp_dir.path().join("2023")).unwrap();
+        notes.save_all(&batch).unwrap();
+        assert_eq!(notes.load(&note.relative_path()).unwrap(), note);
+        assert_eq!(notes.load(&other.relative_path()).unwrap(), other);
+    }
+
+    #[test]
+    fn test_validate_reports_all_issues() {
+        let (temp_dir, notes) = temp_repository();
+        notes.save(&make_note()).unwrap();
+        let base_path = temp_dir.path();
+        std::fs::create_dir(base_path.join("2021")).unwrap();
+        let misfiled = Path::new("2021").join("20220707T142708--duplicate__k1.md");
+        std::fs::write(base_path.join(&misfiled), make_note().dump()).unwrap();
+        let invalid = Path::new("2022").join("20220708T101010--invalid__k1.md");
+        std::fs::write(base_path.join(&invalid), "no front matter").unwrap();
+        let no_keywords = make_note_with("20220709T101010", "No keywords");
+        let no_keywords_path = notes.save(&no_keywords).unwrap();
+
+        let issues = notes.validate().unwrap();
+
+        let has_issue = |path: &Path, kind| issues.iter().any(|i| i.path == path && i.kind == kind);
+        assert!(has_issue(&misfiled, IssueKind::MisfiledYear));
+        assert!(has_issue(&invalid, IssueKind::InvalidNote));
+        assert!(has_issue(&no_keywords_path, IssueKind::EmptyKeywords));
+        assert!(has_issue(
+            &make_note().relative_path(),
+            IssueKind::DuplicateId
+        ));
+    }
+
+    #[test]
+    fn test_merge_front_matters() {
+        let parse = |yaml: &str| FrontMatter::parse(yaml).unwrap();
+        let first = parse("title: First\ndate: 2022-07-08 10:00:00\nkeywords: k1 k2\n");
+        let second = parse("title: Second\ndate: 2022-07-07 10:00:00\nkeywords: k2 k3\n");
+
+        let merged = first.merge_with(&second);
+        assert_eq!(merged.title(), Some("First"));
+        assert_eq!(merged.date, "2022-07-07 10:00:00");
+        assert_eq!(merged.keywords(), vec!["k1", "k2", "k3"]);
+
+        let untitled = parse("title: ''\ndate: not a date\nkeywords: ''\n");
+        let merged = untitled.merge_with(&first);
+        assert_eq!(merged.title(), Some("First"));
+        assert_eq!(merged.date, "2022-07-08 10:00:00");
+        assert_eq!(merged.keywords(), vec!["k1", "k2"]);
+
+        let merged = first.merge_with(&untitled);
+        assert_eq!(merged.date, "2022-07-08 10:00:00");
+        assert_eq!(merged.keywords(), vec!["k1", "k2"]);
+    }
+
+    #[test]
+    fn test_merge_notes() {
+        let first = make_note();
+        let second = NoteBuilder::new()
+            .id(Id::from_str("20220708T101010").unwrap())
+            .title("Second")
+            .keyword("k3")
+            .text("Second text")
+            .build()
+            .unwrap();
+
+        let merged = first.merge_bodies(&second);
+
+        assert_eq!(merged.id(), first.id());
+        assert_eq!(merged.metadata().title(), Some("This is a title"));
+        assert_eq!(merged.metadata().keywords(), &["k1", "k2", "k3"]);
+        assert_eq!(merged.text, "This is my note\n\n---\n\nSecond text\n");
+
+        let empty = Note::new(first.metadata.clone(), String::new());
+        assert_eq!(empty.merge_bodies(&second).text, "Second text\n");
+        assert_eq!(first.merge_bodies(&empty).text, first.text);
+    }
+
+    #[test]
+    fn test_sanitize_keyword() {
+        assert_eq!(sanitize_keyword("Rust").unwrap(), "rust");
+        assert_eq!(sanitize_keyword("to_do list").unwrap(), "todolist");
+        assert_eq!(sanitize_keyword("Élan").unwrap(), "elan");
+        assert!(sanitize_keyword("--").is_err());
+    }
+
+    #[test]
+    fn test_append_and_prepend_text() {
+        let mut note = make_note();
+        let front_matter = note.front_matter();
+
+        note.append_text("## References\n");
+        note.prepend_text("# Intro");
+
+        assert!(note
+            .dump()
+            .ends_with("---\n# Intro\n\nThis is my note\n\n## References\n"));
+        assert_eq!(note.front_matter(), front_matter);
+
+        let mut empty = Note::new(make_note().metadata, String::new());
+        empty.append_text("first");
+        assert_eq!(empty.text, "first\n");
+    }
+
+    #[test]
+    fn test_edit_by_id() {
+        let (temp_dir, notes) = temp_repository();
+        notes.save(&make_note()).unwrap();
+        let id = Id::from_str("20220707T142708").unwrap();
+
+        let new_path = notes
+            .edit(&id, |note| {
+                note.add_keyword("k3");
+                note.append_text("More text");
+            })
+            .unwrap();
+
+        let expected = Path::new("2022").join("20220707T142708--this-is-a-title__k1_k2_k3.md");
+        assert_eq!(new_path, temp_dir.path().join(&expected));
+        let note = notes.load(&expected).unwrap();
+        assert_eq!(note.text, "This is my note\n\nMore text\n");
+        assert_eq!(notes.count().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_body_text_and_markdown() {
+        let note = make_note();
+
+        assert_eq!(note.body_text(), "This is my note\n");
+        assert_eq!(note.body_text(), note.text());
+        let markdown = note.as_markdown();
+        assert!(markdown.starts_with("title: This is a title\n"));
+        assert_eq!(format!("---\n{markdown}"), note.dump());
+    }
+
+    #[test]
+    fn test_notes_in_year() {
+        let (_temp_dir, notes) = temp_repository();
+        let later = make_note_with("20221231T101010", "Later");
+        let other_year = make_note_with("20230101T101010", "Other year");
+        for note in [&make_note(), &later, &other_year] {
+            notes.save(note).unwrap();
+        }
+
+        let in_2022 = notes.notes_in_year(2022).unwrap();
+
+        assert_eq!(
+            in_2022,
+            vec![later.metadata().clone(), make_note().metadata().clone()]
+        );
+        assert!(notes.notes_in_year(1999).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_notes_between() {
+        let (_temp_dir, notes) = temp_repository();
+        let later = make_note_with("20221231T101010", "Later");
+        let next_year = make_note_with("20230101T101010", "Next year");
+        for note in [&make_note(), &later, &next_year] {
+            notes.save(note).unwrap();
+        }
+
+        let between = notes
+            .notes_between(
+                &Id::from_str("20220801T000000").unwrap(),
+                &Id::from_str("20230101T101010").unwrap(),
+            )
+            .unwrap();
+
+        assert_eq!(
+            between,
+            vec![later.metadata().clone(), next_year.metadata().clone()]
+        );
+    }
+
+    #[test]
+    fn test_export_dir() {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("test-denotes")
+            .tempdir()
+            .unwrap();
+        let dest = tempfile::Builder::new()
+            .prefix("test-export")
+            .tempdir()
+            .unwrap();
+        let notes = NotesRepository::open(&temp_dir).unwrap();
+        let relative_path = notes.save(&make_note()).unwrap();
+
+        assert_eq!(
+            notes.export_dir(&dest.path().join("plain"), true).unwrap(),
+            1
+        );
+        assert_eq!(
+            notes.export_dir(&dest.path().join("tags"), false).unwrap(),
+            1
+        );
+
+        let plain =
+            std::fs::read_to_string(dest.path().join("plain").join(&relative_path)).unwrap();
+        assert_eq!(plain, "This is my note\n");
+        let tagged =
+            std::fs::read_to_string(dest.path().join("tags").join(&relative_path)).unwrap();
+        assert!(tagged.starts_with("---\ntitle: This is a title\n"));
+        assert!(tagged.contains("tags:\n"));
+        assert!(!tagged.contains("keywords"));
+        assert!(!tagged.contains("identifier"));
+        assert!(tagged.ends_with("---\nThis is my note\n"));
+
+        let notes = notes.with_config(RepositoryConfig {
+            date_format: DateFormat::DateOnly,
+            ..Default::default()
+        });
+        notes.export_dir(&dest.path().join("dates"), false).unwrap();
+        let dated =
+            std::fs::read_to_string(dest.path().join("dates").join(&relative_path)).unwrap();
+        assert!(dated.contains("date: 2022-07-07\n"));
+    }
+
+    #[test]
+    fn test_parse_front_matter_with_missing_or_empty_title() {
+        let front_matter = FrontMatter::parse("title:\ndate: 2022-07-07\nkeywords: k1\n").unwrap();
+        assert_eq!(front_matter.title(), None);
+        assert_eq!(front_matter.slug(), "");
+
+        let front_matter = FrontMatter::parse("date: 2022-07-07\nkeywords: k1\n").unwrap();
+        assert_eq!(front_matter.title(), None);
+
+        let front_matter =
+            FrontMatter::parse("title: ''\ndate: 2022-07-07\nkeywords: k1\n").unwrap();
+        assert_eq!(front_matter.title(), Some(""));
+
+        let id = Id::from_str("20220707T142708").unwrap();
+        let untitled = Metadata::new(id, None, vec!["k1".to_owned()], "md".to_owned());
+        assert_eq!(
+            untitled.relative_path(),
+            Path::new("2022").join("20220707T142708--untitled__k1.md")
+        );
+        let parsed = FrontMatter::parse(&untitled.front_matter().dump()).unwrap();
+        assert_eq!(parsed.title(), None);
+    }
+
+    #[test]
+    fn test_display() {
+        let note = make_note();
+        assert_eq!(note.metadata().id.to_string(), "20220707T142708");
+        assert_eq!(
+            note.metadata().to_string(),
+            "20220707T142708 -- This is a title [k1, k2]"
+        );
+        assert_eq!(
+            note.to_string(),
+            "20220707T142708 -- This is a title [k1, k2]"
+        );
+    }
+
+    #[test]
+    fn test_retitle() {
+        let (temp_dir, notes) = temp_repository();
+        let note = make_note();
+        let old_path = notes.save(&note).unwrap();
+
+        let new_path = notes.retitle(&note.metadata.id, "New title").unwrap();
+
+        assert_eq!(
+            new_path,
+            temp_dir
+                .path()
+                .join("2022/20220707T142708--new-title__k1_k2.md")
+        );
+        assert!(!temp_dir.path().join(old_path).exists());
+        let loaded = notes.load_absolute(&new_path).unwrap();
+        assert_eq!(loaded.metadata().title(), Some("New title"));
+        assert_eq!(loaded.metadata().keywords(), ["k1", "k2"]);
+        assert_eq!(loaded.text(), note.text());
+    }
+
+    #[test]
+    fn test_parse_tags_as_keywords() {
+        let front_matter = FrontMatter::parse("title: t\ndate: 2022-07-07\ntags: k1 k2\n").unwrap();
+        assert_eq!(front_matter.keywords(), ["k1", "k2"]);
+        assert!(front_matter.dump().contains("keywords: k1 k2\n"));
+        assert!(!front_matter.dump().contains("tags"));
+
+        let front_matter =
+            FrontMatter::parse("title: t\ndate: 2022-07-07\ntags: [k1, k2]\n").unwrap();
+        assert_eq!(front_matter.keywords(), ["k1", "k2"]);
+    }
+
+    #[test]
+    fn test_parse_keywords_as_a_list() {
+        let front_matter =
+            FrontMatter::parse("title: t\ndate: 2022-07-07\nkeywords:\n- k1\n- k2\n").unwrap();
+        assert_eq!(front_matter.keywords(), ["k1", "k2"]);
+        assert!(front_matter.dump().contains("keywords: k1 k2\n"));
+    }
+
+    #[test]
+    fn test_filter_and_filter_by_filename() {
+        let (_temp_dir, notes) = temp_repository();
+        let first = notes.save(&make_note()).unwrap();
+        let other = NoteBuilder::new()
+            .id(Id::from_str("20230101T101010").unwrap())
+            .title("Other")
+            .keyword("k3")
+            .text("Some other text")
+            .build()
+            .unwrap();
+        let second = notes.save(&other).unwrap();
+
+        let in_2023 = notes.filter_by_filename(|info| info.id().year() == "2023");
+        assert_eq!(in_2023.unwrap(), vec![second]);
+        let with_k1 = notes.filter_by_filename(|info| info.keywords().contains(&"k1".to_owned()));
+        assert_eq!(with_k1.unwrap(), vec![first]);
+
+        let matching = notes.filter(|note| note.text().contains("other")).unwrap();
+        assert_eq!(matching, vec![other]);
+        assert_eq!(notes.filter(|_| true).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_export_ndjson() {
+        let (_temp_dir, notes) = temp_repository();
+        let other = make_note_with("20220708T101010", "Other");
+        notes.save(&make_note()).unwrap();
+        notes.save(&other).unwrap();
+
+        let mut out = vec![];
+        let exported = notes.export_ndjson(&mut out).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(exported, notes.count().unwrap());
+        assert_eq!(lines.len(), exported);
+        for line in lines {
+            assert!(serde_json::from_str::<serde_json::Value>(line).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_save_full() {
+        let (temp_dir, notes) = temp_repository();
+
+        let full_path = notes.save_full(&make_note()).unwrap();
+
+        assert_eq!(
+            full_path,
+            temp_dir
+                .path()
+                .join("2022/20220707T142708--this-is-a-title__k1_k2.md")
+        );
+        assert!(full_path.is_file());
+        assert_eq!(notes.load_absolute(&full_path).unwrap(), make_note());
+    }
+
+    #[test]
+    fn test_load_by_id() {
+        let (_temp_dir, notes) = temp_repository();
+        let note = make_note();
+        notes.save(&note).unwrap();
+
+        assert_eq!(notes.load_by_id(&note.metadata.id).unwrap(), Some(note));
+        let missing = Id::from_str("20220708T101010").unwrap();
+        assert_eq!(notes.load_by_id(&missing).unwrap(), None);
+
+        let duplicate = NoteBuilder::new()
+            .id(make_note().metadata.id)
+            .title("Other")
+            .build()
+            .unwrap();
+        notes.save(&duplicate).unwrap();
+        assert!(notes.load_by_id(&make_note().metadata.id).is_err());
     }
 
     #[test]
-    fn test_slugify_title_when_creating_metadata() {
+    fn test_fuzzy_score() {
+        assert_eq!(fuzzy_score("mt", "Mattress"), Some(4));
+        assert_eq!(fuzzy_score("mt", "My title"), Some(6));
+        assert_eq!(fuzzy_score("tm", "My title"), None);
+        assert_eq!(fuzzy_score("", "anything"), Some(0));
+    }
+
+    #[test]
+    fn test_search_title() {
+        let (_temp_dir, notes) = temp_repository();
+        let mattress = make_note_with("20220708T101010", "Mattress");
+        let my_title = make_note_with("20220709T101010", "My title");
+        for note in [&make_note(), &mattress, &my_title] {
+            notes.save(note).unwrap();
+        }
+
+        let found = notes.search_title("mt").unwrap();
+
+        assert_eq!(
+            found,
+            vec![my_title.metadata().clone(), mattress.metadata().clone()]
+        );
+    }
+
+    #[test]
+    fn test_new_note_headless_with_title_and_keywords() {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("test-denotes")
+            .tempdir()
+            .unwrap();
+        let options = cli::NewNoteOptions {
+            title: Some("My title: with a colon".to_owned()),
+            keywords: vec!["k1".to_owned(), "k2".to_owned()],
+            ..Default::default()
+        };
+
+        let relative_path = cli::new_note_headless(temp_dir.path(), &options).unwrap();
+
+        let notes = NotesRepository::open(&temp_dir).unwrap();
+        let note = notes.load(&relative_path).unwrap();
+        assert_eq!(note.metadata().title(), Some("My title: with a colon"));
+        assert_eq!(note.metadata().keywords(), ["k1", "k2"]);
+        let name = name_from_relative_path(&relative_path);
+        assert!(name.ends_with("--my-title-with-a-colon__k1_k2.md"));
+    }
+
+    #[test]
+    fn test_save_and_load_a_note_without_title() {
+        let (_temp_dir, notes) = temp_repository();
         let id = Id::from_str("20220707T142708").unwrap();
-        let title = "This is a title".to_owned();
-        let keywords = vec!["k1".to_owned(), "k2".to_owned()];
-        let extension = "md".to_owned();
-        let metadata = Metadata::new(id, title, keywords, extension);
+        let metadata = Metadata::new(id, None, vec!["k1".to_owned()], "md".to_owned());
+        let note = Note::new(metadata, "text\n".to_owned());
 
-        assert_eq!(metadata.slug(), "this-is-a-title");
+        let relative_path = notes.save(&note).unwrap();
+
+        let info = parse_file_name(&relative_path).unwrap();
+        assert_eq!(info.slug(), UNTITLED_SLUG);
+        let loaded = notes.load(&relative_path).unwrap();
+        assert_eq!(loaded.metadata().title(), None);
+        assert_eq!(loaded.relative_path(), relative_path);
+        assert!(notes.validate().unwrap().is_empty());
     }
 
     #[test]
-    fn test_parse_info_from_file_name() {
-        let name = "20220707T142708--this-is-a-title__k1_k2.md";
+    fn test_allowed_extensions() {
+        let config = RepositoryConfig {
+            allowed_extensions: Some(vec!["md".into()]),
+            ..Default::default()
+        };
+        let (temp_dir, notes) = temp_repository();
+        let notes = notes.with_config(config.clone());
+        let org = NoteBuilder::new()
+            .id(Id::from_str("20220708T101010").unwrap())
+            .title("Other")
+            .extension("org")
+            .build()
+            .unwrap();
 
-        let file_info = parse_file_name(name).unwrap();
+        notes.save(&make_note()).unwrap();
+        assert!(matches!(notes.save(&org), Err(ParseError(_))));
 
-        assert_eq!(file_info.id.as_str(), "20220707T142708");
-        assert_eq!(file_info.slug.as_str(), "this-is-a-title");
-        assert_eq!(&file_info.keywords, &["k1", "k2"]);
-        assert_eq!(file_info.extension.as_str(), "md");
+        let name = "20220708T101010--other__.xyz";
+        std::fs::write(temp_dir.path().join("2022").join(name), "---\n").unwrap();
+        assert!(parse_file_name(name).is_ok());
+        assert!(matches!(
+            parse_file_name_with_config(name, &config),
+            Err(ParseError(_))
+        ));
+        assert_eq!(notes.count().unwrap(), 1);
+        assert!(notes.load(&Path::new("2022").join(name)).is_err());
     }
 
     #[test]
-    fn test_generate_suitable_file_path_for_note() {
+    fn test_normalize_front_matter_keywords() {
+        let contents = "title: t\ndate: 2022-07-07\nkeywords: Rust python rust\n";
+        let mut front_matter = FrontMatter::parse(contents).unwrap();
+        assert_eq!(front_matter.sorted_keywords(), ["python", "rust"]);
+        assert_eq!(front_matter.keywords(), ["Rust", "python", "rust"]);
+
+        front_matter.normalize();
+        let parsed = FrontMatter::parse(&front_matter.dump()).unwrap();
+        assert_eq!(parsed.keywords(), ["python", "rust"]);
+
+        let config = RepositoryConfig {
+            normalize_keywords: KeywordNormalization::Sort,
+            ..Default::default()
+        };
+        let parsed = FrontMatter::parse_with_config(contents, &config).unwrap();
+        assert_eq!(parsed, front_matter);
+    }
+
+    #[test]
+    fn test_normalize_keywords_when_loading() {
+        let config = RepositoryConfig {
+            normalize_keywords: KeywordNormalization::Sort,
+            ..Default::default()
+        };
+        let (temp_dir, notes) = temp_repository();
+        let notes = notes.with_config(config);
+        let year_path = temp_dir.path().join("2022");
+        std::fs::create_dir(&year_path).unwrap();
+        let relative_path = Path::new("2022").join("20220707T142708--t__k2_k1.md");
+        let contents = "---\ntitle: t\ndate: 2022-07-07\nkeywords: K2 k1 k2\n---\n";
+        std::fs::write(temp_dir.path().join(&relative_path), contents).unwrap();
+
+        let note = notes.load(&relative_path).unwrap();
+        assert_eq!(note.metadata().keywords(), ["k1", "k2"]);
+        let metadata = notes.load_metadata(&relative_path).unwrap();
+        assert_eq!(metadata.keywords(), ["k1", "k2"]);
+    }
+
+    #[test]
+    fn test_keywords_are_lowercased() {
+        let id = Id::from_str("20220707T142708").unwrap();
+        let keywords = vec!["RUST".to_owned(), " rust ".to_owned(), "Python".to_owned()];
+        let metadata = Metadata::new(
+            id.clone(),
+            Some("t".to_owned()),
+            keywords.clone(),
+            "md".to_owned(),
+        );
+        assert_eq!(metadata.keywords(), ["rust", "python"]);
+        let metadata = Metadata::new_with_normalization(
+            id.clone(),
+            Some("t".to_owned()),
+            keywords,
+            "md".to_owned(),
+            KeywordNormalization::Sort,
+        );
+        assert_eq!(metadata.keywords(), ["python", "rust"]);
+
+        let builder = || {
+            NoteBuilder::new()
+                .id(id.clone())
+                .title("t")
+                .keyword("RUST")
+                .keyword("rust")
+        };
+        let note = builder().build().unwrap();
+        assert_eq!(note.metadata().keywords(), ["rust"]);
+        let note = builder()
+            .normalize_keywords(KeywordNormalization::Keep)
+            .build()
+            .unwrap();
+        assert_eq!(note.metadata().keywords(), ["RUST", "rust"]);
+
+        let info = parse_file_name("20220707T142708--t__RUST_rust.md").unwrap();
+        assert_eq!(info.keywords(), ["rust"]);
+        let config = RepositoryConfig {
+            normalize_keywords: KeywordNormalization::Keep,
+            ..Default::default()
+        };
+        let info =
+            parse_file_name_with_config("20220707T142708--t__RUST_rust.md", &config).unwrap();
+        assert_eq!(info.keywords(), ["RUST", "rust"]);
+        assert_eq!(
+            info.into_metadata(Some("t".to_owned())).keywords(),
+            ["RUST", "rust"]
+        );
+    }
+
+    #[test]
+    fn test_keep_keyword_case_when_loading() {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("test-denotes")
+            .tempdir()
+            .unwrap();
+        let year_path = temp_dir.path().join("2022");
+        std::fs::create_dir(&year_path).unwrap();
+        let relative_path = Path::new("2022").join("20220707T142708--t__RUST_rust.md");
+        let contents = "---\ntitle: t\ndate: 2022-07-07\nkeywords: RUST rust\n---\n";
+        std::fs::write(temp_dir.path().join(&relative_path), contents).unwrap();
+
+        let notes = NotesRepository::open(&temp_dir).unwrap();
+        assert_eq!(
+            notes.load(&relative_path).unwrap().metadata().keywords(),
+            ["rust"]
+        );
+        assert_eq!(
+            notes.load_metadata(&relative_path).unwrap().keywords(),
+            ["rust"]
+        );
+
+        let config = RepositoryConfig {
+            normalize_keywords: KeywordNormalization::Keep,
+            ..Default::default()
+        };
+        let notes = notes.with_config(config);
+        let note = notes.load(&relative_path).unwrap();
+        assert_eq!(note.metadata().keywords(), ["RUST", "rust"]);
+        assert_eq!(note.relative_path(), relative_path);
+        assert_eq!(
+            notes.load_metadata(&relative_path).unwrap().keywords(),
+            ["RUST", "rust"]
+        );
+
+        let markdown_path = temp_dir.path().join("imported.md");
+        let contents = "---\ntitle: imported\ndate: 2022-07-07\nkeywords: Python python\n---\n";
+        std::fs::write(&markdown_path, contents).unwrap();
+        let imported = notes.import_from_markdown(&markdown_path).unwrap();
+        assert!(imported
+            .to_string_lossy()
+            .ends_with("--imported__Python_python.md"));
+    }
+
+    #[test]
+    fn test_keep_keyword_case_when_querying() {
+        let config = RepositoryConfig {
+            normalize_keywords: KeywordNormalization::Keep,
+            ..Default::default()
+        };
+        let (temp_dir, notes) = temp_repository();
+        let notes = notes.with_config(config.clone());
+        let note = NoteBuilder::new()
+            .id(Id::from_str("20220707T142708").unwrap())
+            .title("t")
+            .keyword("RUST")
+            .keyword("k1")
+            .normalize_keywords(KeywordNormalization::Keep)
+            .build()
+            .unwrap();
+        let relative_path = notes.save(&note).unwrap();
+
+        assert_eq!(
+            notes.find_by_keyword("RUST").unwrap(),
+            vec![relative_path.clone()]
+        );
+        assert!(notes.find_by_keyword("rust").unwrap().is_empty());
+        assert_eq!(
+            notes.notes_by_keyword().unwrap()["RUST"],
+            vec![relative_path.clone()]
+        );
+        let indexed = NotesRepository::open_indexed(&temp_dir)
+            .unwrap()
+            .with_config(config);
+        indexed.build_index().unwrap();
+        assert_eq!(
+            indexed.find_by_keyword("RUST").unwrap(),
+            vec![relative_path]
+        );
+
+        let renamed = notes.rename_keyword_globally("RUST", "python").unwrap();
+
+        assert_eq!(
+            renamed,
+            [Path::new("2022").join("20220707T142708--t__python_k1.md")]
+        );
+        assert!(notes.find_by_keyword("RUST").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_attachments_for() {
+        let (temp_dir, notes) = temp_repository();
         let note = make_note();
+        notes.save(&note).unwrap();
+        let year_path = temp_dir.path().join("2022");
+        for name in [
+            "20220707T142708--this-is-a-title__k1_k2.png",
+            "20220707T142708--scan.pdf",
+            "20220708T101010--other__k1.png",
+        ] {
+            std::fs::write(year_path.join(name), "").unwrap();
+        }
+
+        let attachments = notes.attachments_for(&note.metadata.id).unwrap();
+
         assert_eq!(
-            note.relative_path().to_string_lossy(),
-            "2022/20220707T142708--this-is-a-title__k1_k2.md"
+            attachments,
+            vec![
+                Path::new("2022").join("20220707T142708--scan.pdf"),
+                Path::new("2022").join("20220707T142708--this-is-a-title__k1_k2.png"),
+            ]
+        );
+        let notes = notes.with_extensions(&["md"]);
+        assert_eq!(
+            notes.attachments_for(&note.metadata.id).unwrap(),
+            attachments
         );
+        assert_eq!(notes.count().unwrap(), 1);
     }
 
     #[test]
-    fn test_error_when_trying_to_load_notes_from_a_file() {
-        NotesRepository::open("src/lib.rs").unwrap_err();
+    fn test_find_duplicate_slugs() {
+        let (_temp_dir, notes) = temp_repository();
+        let same_title = make_note_with("20220708T101010", "This is a title");
+        let other = make_note_with("20220709T101010", "Other");
+        let mut expected = vec![];
+        for note in [&make_note(), &same_title, &other] {
+            expected.push(notes.save(note).unwrap());
+        }
+        expected.pop();
+
+        let duplicates = notes.find_duplicate_slugs().unwrap();
+
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates["this-is-a-title"], expected);
     }
 
     #[test]
-    fn test_saving_and_loading() {
+    fn test_move_note() {
+        let source_dir = tempfile::Builder::new()
+            .prefix("test-denotes")
+            .tempdir()
+            .unwrap();
+        let dest_dir = tempfile::Builder::new()
+            .prefix("test-denotes")
+            .tempdir()
+            .unwrap();
+        let source = NotesRepository::open(&source_dir).unwrap();
+        let dest = NotesRepository::open(&dest_dir).unwrap();
+        let note = make_note();
+        let relative_path = source.save(&note).unwrap();
+
+        let new_path = source.move_note(&relative_path, &dest).unwrap();
+
+        assert_eq!(new_path, relative_path);
+        assert!(!source_dir.path().join(&relative_path).exists());
+        assert_eq!(dest.load(&new_path).unwrap(), note);
+
+        source.save(&note).unwrap();
+        assert!(source.move_note(&relative_path, &dest).is_err());
+        assert!(source_dir.path().join(&relative_path).exists());
+    }
+
+    #[test]
+    fn test_io_errors_convert_to_error() {
+        fn read_missing(path: &Path) -> Result<String> {
+            Ok(std::fs::read_to_string(path)?)
+        }
+
+        let err = read_missing(Path::new("/does/not/exist")).unwrap_err();
+
+        match err {
+            Io { source, .. } => assert_eq!(source.kind(), std::io::ErrorKind::NotFound),
+            other => panic!("expected an Io error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_load_note_starting_with_a_bom() {
+        let (temp_dir, notes) = temp_repository();
+        std::fs::create_dir(temp_dir.path().join("2022")).unwrap();
+        let relative_path = make_note().relative_path();
+        let contents = format!("\u{FEFF}{}", make_note().dump());
+        std::fs::write(temp_dir.path().join(&relative_path), contents).unwrap();
+
+        assert_eq!(notes.load(&relative_path).unwrap(), make_note());
+        assert_eq!(
+            &notes.load_metadata(&relative_path).unwrap(),
+            make_note().metadata()
+        );
+    }
+
+    #[test]
+    fn test_with_extensions() {
+        let (temp_dir, notes) = temp_repository();
+        let org = NoteBuilder::new()
+            .id(Id::from_str("20220708T101010").unwrap())
+            .title("Org")
+            .extension("org")
+            .build()
+            .unwrap();
+        let tex = NoteBuilder::new()
+            .id(Id::from_str("20220709T101010").unwrap())
+            .title("Tex")
+            .extension("tex")
+            .build()
+            .unwrap();
+        for note in [&make_note(), &org, &tex] {
+            notes.save(note).unwrap();
+        }
+        let html = "20220707T142708--this-is-a-title__k1_k2.html";
+        std::fs::write(temp_dir.path().join("2022").join(html), "<html>").unwrap();
+
+        assert_eq!(notes.count().unwrap(), 4);
+        let notes = notes.with_extensions(&["md", "tex"]);
+        assert_eq!(notes.count().unwrap(), 2);
+        assert!(notes.load(&org.relative_path()).is_err());
+        assert_eq!(notes.load(&tex.relative_path()).unwrap(), tex);
+    }
+
+    #[test]
+    fn test_id_unix_secs_round_trip() {
+        for (secs, id) in [
+            (0, "19700101T000000"),
+            (1_657_204_028, "20220707T142708"),
+            (951_782_400, "20000229T000000"),
+            (253_402_300_799, "99991231T235959"),
+        ] {
+            let from_secs = Id::from_unix_secs(secs).unwrap();
+            assert_eq!(from_secs.as_str(), id);
+            assert_eq!(from_secs.to_unix_secs().unwrap(), secs);
+        }
+        assert!(Id::from_unix_secs(i64::MAX / 2).is_err());
+        assert!(Id::from_unix_secs(253_402_300_800).is_err());
+    }
+
+    #[test]
+    fn test_new_note_from_template() {
         let temp_dir = tempfile::Builder::new()
             .prefix("test-denotes")
             .tempdir()
             .unwrap();
+        let templates_dir = tempfile::Builder::new()
+            .prefix("test-templates")
+            .tempdir()
+            .unwrap();
+        let template = "---\ndate: {{date}}\ntitle: Meeting\nkeywords: meeting {{keywords}}\n---\n## Attendees\n";
+        std::fs::write(templates_dir.path().join("meeting.md"), template).unwrap();
+        let mut options = cli::NewNoteOptions {
+            keywords: vec!["work".to_owned()],
+            template_name: Some("meeting".to_owned()),
+            templates_dir: Some(templates_dir.path().to_owned()),
+            ..Default::default()
+        };
         let notes = NotesRepository::open(&temp_dir).unwrap();
-        let note = make_note();
-        notes.save(&note).unwrap();
 
-        let relative_path = &note.relative_path();
-        let saved = notes.load(relative_path).unwrap();
-        assert_eq!(note, saved);
+        let relative_path = cli::new_note_headless(temp_dir.path(), &options).unwrap();
+
+        let note = notes.load(&relative_path).unwrap();
+        assert_eq!(note.metadata().title(), Some("Meeting"));
+        assert_eq!(note.metadata().keywords(), ["meeting", "work"]);
+        assert_eq!(note.text(), "## Attendees\n");
+
+        options.template_name = Some("unknown".to_owned());
+        let relative_path = cli::new_note_headless(temp_dir.path(), &options).unwrap();
+        let note = notes.load(&relative_path).unwrap();
+        assert_eq!(note.metadata().title(), None);
+        assert_eq!(note.metadata().keywords(), ["work"]);
+    }
+
+    #[test]
+    fn test_notes_created_on() {
+        let now = Id::from_str("20220707T142708")
+            .unwrap()
+            .to_offset_date_time()
+            .unwrap();
+        let (_temp_dir, notes) = temp_repository();
+        let notes = notes.with_clock(Arc::new(FixedClock(now)));
+        let mut saved = vec![];
+        for (id, title) in [
+            ("20220630T235959", "Last month"),
+            ("20220704T000000", "Monday"),
+            ("20220707T235959", "Same day"),
+            ("20220711T000000", "Next week"),
+        ] {
+            let note = make_note_with(id, title);
+            notes.save(&note).unwrap();
+            saved.push(note);
+        }
+        notes.save(&make_note()).unwrap();
+        let [last_month, monday, same_day, next_week] = <[Note; 4]>::try_from(saved).unwrap();
+
+        assert_eq!(
+            notes.notes_created_on(2022, 7, 7).unwrap(),
+            vec![make_note(), same_day.clone()]
+        );
+        assert_eq!(
+            notes.notes_created_this_week().unwrap(),
+            vec![monday.clone(), make_note(), same_day.clone()]
+        );
+        assert_eq!(
+            notes.notes_created_this_month().unwrap(),
+            vec![monday, make_note(), same_day, next_week]
+        );
+        assert_eq!(
+            notes.notes_created_on(2022, 6, 30).unwrap(),
+            vec![last_month]
+        );
+        assert!(notes.notes_created_on(2022, 13, 1).is_err());
+        assert!(notes.notes_created_on(2022, 2, 30).is_err());
+    }
+
+    #[test]
+    fn test_relink() {
+        let (_temp_dir, notes) = temp_repository();
+        let linking = NoteBuilder::new()
+            .id(Id::from_str("20220708T101010").unwrap())
+            .title("Linking")
+            .text("See [the note](denote:20220707T142708) and denote:20220707T142708\n")
+            .build()
+            .unwrap();
+        let linking_path = notes.save(&linking).unwrap();
+        notes.save(&make_note()).unwrap();
+        let old = Id::from_str("20220707T142708").unwrap();
+        let new = Id::from_str("20220709T101010").unwrap();
+
+        let changed = notes.relink(&old, &new).unwrap();
+
+        assert_eq!(changed, vec![linking_path.clone()]);
+        assert_eq!(
+            notes.load(&linking_path).unwrap().text(),
+            "See [the note](denote:20220709T101010) and denote:20220709T101010\n"
+        );
+        assert_eq!(
+            notes.load(&make_note().relative_path()).unwrap(),
+            make_note()
+        );
+    }
+
+    #[test]
+    fn test_filter_keywords_any_and_all() {
+        let (_temp_dir, notes) = temp_repository();
+        let first = notes.save(&make_note()).unwrap();
+        let other = NoteBuilder::new()
+            .id(Id::from_str("20220708T142708").unwrap())
+            .title("Other")
+            .keyword("k2")
+            .keyword("k3")
+            .build()
+            .unwrap();
+        let second = notes.save(&other).unwrap();
+
+        for (keywords, any, all) in [
+            (vec!["k1"], vec![&first], vec![&first]),
+            (vec!["k1", "k2"], vec![&first, &second], vec![&first]),
+            (vec!["k1", "k3"], vec![&first, &second], vec![]),
+            (vec!["k2", "k3"], vec![&first, &second], vec![&second]),
+            (vec!["k4"], vec![], vec![]),
+        ] {
+            let any: Vec<_> = any.into_iter().cloned().collect();
+            let all: Vec<_> = all.into_iter().cloned().collect();
+            assert_eq!(
+                notes.filter_keywords_any(&keywords).unwrap(),
+                any,
+                "any {keywords:?}"
+            );
+            assert_eq!(
+                notes.filter_keywords_all(&keywords).unwrap(),
+                all,
+                "all {keywords:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_front_matter_new() {
+        let front_matter = FrontMatter::new(
+            Some("Title".to_string()),
+            "2022-07-07 14:27:08".to_string(),
+            vec!["k1".to_string(), "k2".to_string()],
+        );
+
+        assert_eq!(
+            front_matter.dump(),
+            "---\ntitle: Title\ndate: \"2022-07-07 14:27:08\"\nkeywords: k1 k2\n"
+        );
+        assert_eq!(
+            FrontMatter::parse(&front_matter.dump()).unwrap(),
+            front_matter
+        );
+    }
+
+    #[test]
+    fn test_clone_with_new_id() {
+        let contents = "---\ntitle: This is a title\ndate: 2022-07-07T14:27:08+02:00\nkeywords: k1 k2\naliases: first\n---\nThis is my note\n";
+        let note = get_note_from_markdown(
+            Id::from_str("20220707T122708").unwrap(),
+            contents.to_string(),
+            Default::default(),
+        )
+        .unwrap();
+        let new_id = Id::from_str("20220801T101010").unwrap();
+
+        let clone = note.clone_with_new_id(new_id.clone());
+
+        assert_eq!(clone.id(), new_id.as_str());
+        assert_eq!(clone.metadata().title(), Some("This is a title"));
+        assert_eq!(clone.metadata().keywords(), note.metadata().keywords());
+        assert_eq!(clone.text(), note.text());
+        assert!(clone.metadata().aliases().is_empty());
+        assert_eq!(clone.front_matter().date_as_id().unwrap(), new_id);
+        assert_eq!(
+            clone.relative_path(),
+            Path::new("2022").join("20220801T101010--this-is-a-title__k1_k2.md")
+        );
+        assert_ne!(note.clone_with_now().id(), note.id());
+
+        let note = NoteBuilder::new()
+            .id(Id::from_str("20220707T142708").unwrap())
+            .title("t")
+            .keyword("Zed")
+            .keyword("Alpha")
+            .normalize_keywords(KeywordNormalization::Keep)
+            .build()
+            .unwrap();
+        let clone = note.clone_with_new_id(new_id);
+        assert_eq!(clone.metadata().keywords(), ["Zed", "Alpha"]);
+    }
+
+    #[test]
+    fn test_word_count_and_reading_time() {
+        let note = NoteBuilder::new()
+            .id(Id::from_str("20220707T142708").unwrap())
+            .title("Words")
+            .text("Some code:\n\n```rust\nlet x = 1;\n```\n")
+            .build()
+            .unwrap();
+
+        assert_eq!(note.word_count(), 8);
+        assert_eq!(note.reading_time_minutes(200), 1);
+        assert_eq!(note.reading_time_minutes(4), 2);
+        assert_eq!(note.reading_time_minutes(3), 3);
+        assert_eq!(note.reading_time_minutes(0), 0);
+        let empty = Note::new(note.metadata().clone(), String::new());
+        assert_eq!(empty.reading_time_minutes(200), 0);
+    }
+
+    #[test]
+    fn test_copy_note() {
+        let now = Id::from_str("20220707T142708")
+            .unwrap()
+            .to_offset_date_time()
+            .unwrap();
+        let (_temp_dir, notes) = temp_repository();
+        let notes = notes.with_clock(Arc::new(FixedClock(now)));
+        let original = notes.save(&make_note()).unwrap();
+
+        let first = notes.copy_note(&original).unwrap();
+        let second = notes.copy_note(&original).unwrap();
+
+        assert_eq!(
+            first,
+            Path::new("2022").join("20220707T142709--this-is-a-title__k1_k2.md")
+        );
+        assert_eq!(
+            second,
+            Path::new("2022").join("20220707T142710--this-is-a-title__k1_k2.md")
+        );
+        assert_eq!(notes.load(&original).unwrap(), make_note());
+        assert_eq!(notes.load(&first).unwrap().text(), make_note().text());
+        assert_eq!(notes.count().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_most_recently_modified() {
+        let (temp_dir, notes) = temp_repository();
+        let mut paths = vec![];
+        for (id, mtime) in [
+            ("20220101T101010", 1_700_000_000),
+            ("20220202T101010", 1_600_000_000),
+            ("20220303T101010", 1_650_000_000),
+        ] {
+            let note = make_note_with(id, "Note");
+            let relative_path = notes.save(&note).unwrap();
+            let mtime = filetime::FileTime::from_unix_time(mtime, 0);
+            filetime::set_file_mtime(temp_dir.path().join(&relative_path), mtime).unwrap();
+            paths.push(relative_path);
+        }
+
+        let recent = notes.most_recently_modified(2).unwrap();
+
+        let recent_paths: Vec<_> = recent.iter().map(|(p, _)| p.clone()).collect();
+        assert_eq!(recent_paths, vec![paths[0].clone(), paths[2].clone()]);
+        let expected = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        assert_eq!(recent[0].1, expected);
+        assert_eq!(notes.most_recently_modified(10).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_duplicate() {
+        let now = Id::from_str("20230101T101010")
+            .unwrap()
+            .to_offset_date_time()
+            .unwrap();
+        let (_temp_dir, notes) = temp_repository();
+        let notes = notes.with_clock(Arc::new(FixedClock(now)));
+        notes.save(&make_note()).unwrap();
+        let id = Id::from_str(make_note().id()).unwrap();
+
+        let new_path = notes.duplicate(&id).unwrap();
+
+        assert_eq!(
+            new_path,
+            Path::new("2023").join("20230101T101010--this-is-a-title__k1_k2.md")
+        );
+        let copy = notes.load(&new_path).unwrap();
+        assert_eq!(copy.text(), make_note().text());
+        assert_eq!(
+            copy.front_matter().date_as_id().unwrap().as_str(),
+            "20230101T101010"
+        );
+        let missing = Id::from_str("20220707T000000").unwrap();
+        notes.duplicate(&missing).unwrap_err();
+    }
+
+    struct FixedClock(OffsetDateTime);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> OffsetDateTime {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_import_with_a_fixed_clock() {
+        let now = Id::from_str("20220707T142708")
+            .unwrap()
+            .to_offset_date_time()
+            .unwrap();
+        let (temp_dir, notes) = temp_repository();
+        let notes = notes.with_clock(Arc::new(FixedClock(now)));
+        let markdown_path = temp_dir.path().join("note.md");
+        let contents = "---\ntitle: This is a title\ndate: 2022-07-07\nkeywords: k1\n---\n";
+        std::fs::write(&markdown_path, contents).unwrap();
+
+        let first = notes.import_from_markdown(&markdown_path).unwrap();
+        let second = notes.import_from_markdown(&markdown_path).unwrap();
+
+        assert_eq!(
+            first,
+            Path::new("2022").join("20220707T142708--this-is-a-title__k1.md")
+        );
+        assert_eq!(
+            second,
+            Path::new("2022").join("20220707T142709--this-is-a-title__k1.md")
+        );
     }
 
     #[test]
@@ -559,6 +5558,28 @@ mod tests {
         assert_eq!(&parsed.title, &original.title);
     }
 
+    #[test]
+    fn test_identifier_is_written_as_a_quoted_string() {
+        let note = make_note();
+        assert!(note.dump().contains("identifier: \"20220707T142708\"\n"));
+    }
+
+    #[test]
+    fn test_error_when_identifier_does_not_match_filename() {
+        let (temp_dir, notes) = temp_repository();
+        let note = make_note();
+        let relative_path = notes.save(&note).unwrap();
+        let full_path = temp_dir.path().join(&relative_path);
+        let contents = std::fs::read_to_string(&full_path).unwrap();
+        std::fs::write(
+            &full_path,
+            contents.replace("20220707T142708", "20220707T000000"),
+        )
+        .unwrap();
+
+        notes.load(&relative_path).unwrap_err();
+    }
+
     #[test]
     #[ignore]
     fn test_load_front_matter_from_contents() {
@@ -1,12 +1,21 @@
 use clap::Parser;
-use denote::{cli, NotesRepository, Result};
+use denote::{cli, Backup, NotesRepository, Result};
 use std::path::PathBuf;
 
 #[derive(Parser)]
 #[clap(version)]
 struct Opts {
-    #[clap(long, help = "Path of the notes repository")]
-    base_path: PathBuf,
+    #[clap(
+        long,
+        help = "Path of the notes repository - defaults to base_path in ~/.config/denote/config.toml"
+    )]
+    base_path: Option<PathBuf>,
+    #[clap(
+        long,
+        global = true,
+        help = "Only print what would be written, renamed or deleted"
+    )]
+    dry_run: bool,
     #[clap(subcommand)]
     action: Action,
 }
@@ -14,32 +23,327 @@ struct Opts {
 #[derive(Parser)]
 enum Action {
     #[clap(about = "Create a new note from scratch")]
-    Create,
+    Create(CreateOpts),
     #[clap(about = "Update an existing note, renaming it if required")]
     Update(UpdateOpts),
+    #[clap(
+        visible_alias = "check",
+        about = "Check all the notes and suggest fixes. Exit with 1 if there are issues, and 2 on errors"
+    )]
+    Validate(ValidateOpts),
+    #[clap(
+        about = "Rename the notes whose path is wrong. Exit with 1 if notes were renamed, and 2 on errors"
+    )]
+    Repair(RepairOpts),
+    #[clap(about = "Add, remove or rename keywords")]
+    Tag(TagOpts),
+    #[clap(about = "Open an existing note in $EDITOR, renaming it if required")]
+    Edit(EditOpts),
+    #[clap(about = "Print a note to stdout")]
+    Show(ShowOpts),
+    #[clap(about = "Create a copy of an existing note with a new id, and open it in $EDITOR")]
+    Clone(CloneOpts),
+    #[clap(
+        about = "Duplicate a note under a new id in the same repository, keeping the original - unlike a move"
+    )]
+    Copy(CopyOpts),
+    #[clap(about = "Print the paths of the notes linking to the given note")]
+    Backlinks(BacklinksOpts),
+    #[clap(about = "Print a summary of the notes")]
+    Stats(StatsOpts),
+    #[clap(about = "Print all the notes")]
+    Export(ExportOpts),
+    #[clap(about = "Create or promote drafts")]
+    Draft(DraftOpts),
+    #[clap(about = "Print the paths of the notes created on the given day")]
+    Daily(DailyOpts),
+    #[clap(about = "Watch the notes and rename them when their front matter changes")]
+    Watch,
+}
+
+#[derive(Parser)]
+struct CreateOpts {
+    #[clap(long, help = "Title of the new note")]
+    title: Option<String>,
+    #[clap(
+        long,
+        help = "Name of a template in ~/.config/denote/templates/, without the .md extension"
+    )]
+    template_name: Option<String>,
+    #[clap(
+        long = "keywords",
+        visible_alias = "keyword",
+        use_value_delimiter = true,
+        help = "Keywords of the new note, separated by commas - can be used several times"
+    )]
+    keywords: Vec<String>,
 }
 
 #[derive(Parser)]
 struct UpdateOpts {
     #[clap(help = "Path of the notes repository")]
     full_path: PathBuf,
+    #[clap(long, help = "Copy the note to <name>.bak before renaming it")]
+    backup: bool,
+    #[clap(long, help = "Copy the note to this directory before renaming it")]
+    backup_dir: Option<PathBuf>,
+}
+
+#[derive(Parser)]
+struct ValidateOpts {
+    #[clap(long, help = "Rename the notes that have a suggested path")]
+    fix: bool,
+    #[clap(long, value_enum, default_value = "text", help = "Output format")]
+    format: cli::OutputFormat,
+}
+
+#[derive(Parser)]
+struct RepairOpts {
+    #[clap(long, help = "Copy all the notes to this directory first")]
+    backup_dir: Option<PathBuf>,
+}
+
+#[derive(Parser)]
+struct EditOpts {
+    #[clap(help = "Beginning of the id of the note, or its slug")]
+    query: String,
+}
+
+#[derive(Parser)]
+struct ShowOpts {
+    #[clap(help = "Beginning of the id of the note, or its slug")]
+    query: String,
+    #[clap(long, help = "Only print the text, without the front matter")]
+    body_only: bool,
+    #[clap(
+        long,
+        conflicts_with = "body-only",
+        help = "Only print the metadata, as JSON"
+    )]
+    metadata: bool,
+}
+
+#[derive(Parser)]
+struct CloneOpts {
+    #[clap(help = "Id of the note to copy")]
+    id: String,
+}
+
+#[derive(Parser)]
+struct CopyOpts {
+    #[clap(help = "Id of the note to duplicate")]
+    source_id: String,
+}
+
+#[derive(Parser)]
+struct BacklinksOpts {
+    #[clap(help = "Id of the note")]
+    id: String,
+}
+
+#[derive(Parser)]
+struct StatsOpts {
+    #[clap(long, help = "Do not load the notes to count words")]
+    fast: bool,
+}
+
+#[derive(Parser)]
+struct ExportOpts {
+    #[clap(long, value_enum, default_value = "json", help = "Output format")]
+    format: cli::OutputFormat,
+    #[clap(
+        long,
+        help = "With --format json, print one note per line as soon as it is loaded"
+    )]
+    stream: bool,
+}
+
+#[derive(Parser)]
+struct DailyOpts {
+    #[clap(help = "Day of creation, like 2022-07-07")]
+    date: String,
+}
+
+#[derive(Parser)]
+struct DraftOpts {
+    #[clap(subcommand)]
+    action: DraftAction,
+}
+
+#[derive(Parser)]
+enum DraftAction {
+    #[clap(about = "Create a new note with the draft keyword")]
+    New,
+    #[clap(about = "Remove the draft keyword from a note, renaming it")]
+    Promote {
+        #[clap(help = "Id of the note")]
+        id: String,
+    },
+}
+
+#[derive(Parser)]
+struct TagOpts {
+    #[clap(subcommand)]
+    action: TagAction,
+}
+
+#[derive(Parser)]
+enum TagAction {
+    #[clap(about = "Add a keyword to a note")]
+    Add {
+        #[clap(help = "Path of the note")]
+        full_path: PathBuf,
+        keyword: String,
+    },
+    #[clap(about = "Remove a keyword from a note")]
+    Remove {
+        #[clap(help = "Path of the note")]
+        full_path: PathBuf,
+        keyword: String,
+    },
+    #[clap(about = "Rename a keyword in all the notes")]
+    Rename {
+        old: String,
+        new: String,
+        #[clap(long, help = "Copy all the notes to this directory first")]
+        backup_dir: Option<PathBuf>,
+    },
+}
+
+/// Exit with 0 on success, 1 when `res` is false, and 2 on errors
+fn exit_with_status(res: Result<bool>) -> ! {
+    let code = match res {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            2
+        }
+    };
+    std::process::exit(code)
+}
+
+/// Read the config file and find the base path, exiting with `code`
+/// when either fails
+fn load_config(base_path: Option<PathBuf>, code: i32) -> (cli::Config, PathBuf) {
+    let config = match cli::Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(code);
+        }
+    };
+    match base_path.or_else(|| config.base_path.clone()) {
+        Some(base_path) => (config, base_path),
+        None => {
+            eprintln!(
+                "--base-path should be set, either on the command line or in the config file"
+            );
+            std::process::exit(code);
+        }
+    }
 }
 
 fn main() -> Result<()> {
     let opts = Opts::parse();
-    let notes = NotesRepository::open(&opts.base_path)?;
+    // Note: validate and repair exit with 2 on all errors, including
+    // the ones happening before they run
+    let code = match opts.action {
+        Action::Validate(_) | Action::Repair(_) => 2,
+        _ => 1,
+    };
+    let (config, base_path) = load_config(opts.base_path, code);
+    let dry_run = opts.dry_run;
     match opts.action {
-        Action::Create => {
-            cli::new_note(&opts.base_path)?;
+        Action::Create(create) => {
+            let mut options = cli::NewNoteOptions::from(&config);
+            options.title = create.title;
+            options.template_name = create.template_name;
+            options.keywords.extend(create.keywords);
+            options.dry_run = dry_run;
+            cli::new_note_with_options(&base_path, &options)?;
             Ok(())
         }
         Action::Update(update) => {
-            let relative_path = pathdiff::diff_paths(&update.full_path, &opts.base_path)
-                .ok_or_else(|| {
+            let relative_path =
+                pathdiff::diff_paths(&update.full_path, &base_path).unwrap_or_else(|| {
                     eprintln!("repository and update paths should be relative to each other");
                     std::process::exit(1);
-                })?;
+                });
+            let notes = NotesRepository::open(&base_path)?.with_dry_run(dry_run);
+            let notes = match (update.backup_dir, update.backup) {
+                (Some(backup_dir), _) => notes.with_backup(Backup::Dir(backup_dir)),
+                (None, true) => notes.with_backup(Backup::Suffix),
+                (None, false) => notes,
+            };
             notes.update(&relative_path)?;
+            cli::print_slug_mismatches(&notes);
+            cli::print_planned_actions(&notes);
+            Ok(())
+        }
+        Action::Validate(validate) => exit_with_status(cli::validate(
+            &base_path,
+            validate.fix,
+            validate.format,
+            dry_run,
+        )),
+        Action::Repair(repair) => exit_with_status(cli::repair(
+            &base_path,
+            dry_run,
+            repair.backup_dir.as_deref(),
+        )),
+        Action::Tag(tag) => {
+            match tag.action {
+                TagAction::Add { full_path, keyword } => {
+                    cli::add_tag(&base_path, &full_path, &keyword, dry_run)?;
+                }
+                TagAction::Remove { full_path, keyword } => {
+                    cli::remove_tag(&base_path, &full_path, &keyword, dry_run)?;
+                }
+                TagAction::Rename {
+                    old,
+                    new,
+                    backup_dir,
+                } => {
+                    cli::rename_tag(&base_path, &old, &new, dry_run, backup_dir.as_deref())?;
+                }
+            }
+            Ok(())
+        }
+        Action::Edit(edit) => cli::edit(&base_path, &edit.query, config.editor.as_deref(), dry_run),
+        Action::Show(show) => {
+            let mode = match (show.body_only, show.metadata) {
+                (true, _) => cli::ShowMode::BodyOnly,
+                (_, true) => cli::ShowMode::Metadata,
+                _ => cli::ShowMode::Full,
+            };
+            cli::show(&base_path, &show.query, mode)
+        }
+        Action::Clone(clone) => {
+            cli::clone_note(&base_path, &clone.id, config.editor.as_deref(), dry_run)?;
+            Ok(())
+        }
+        Action::Copy(copy) => {
+            cli::copy_note(&base_path, &copy.source_id, dry_run)?;
+            Ok(())
+        }
+        Action::Backlinks(backlinks) => cli::backlinks(&base_path, &backlinks.id),
+        Action::Stats(stats) => cli::stats(&base_path, stats.fast),
+        Action::Export(export) => cli::export(&base_path, export.format, export.stream),
+        Action::Watch => cli::watch(&base_path),
+        Action::Daily(daily) => cli::daily(&base_path, &daily.date),
+        Action::Draft(draft) => {
+            match draft.action {
+                DraftAction::New => {
+                    let mut options = cli::NewNoteOptions::from(&config);
+                    options.keywords.push(denote::DRAFT_KEYWORD.to_string());
+                    options.dry_run = dry_run;
+                    cli::new_note_with_options(&base_path, &options)?;
+                }
+                DraftAction::Promote { id } => {
+                    cli::promote_draft(&base_path, &id, dry_run)?;
+                }
+            }
             Ok(())
         }
     }
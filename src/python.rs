@@ -1,10 +1,36 @@
+// Note: the macros of pyo3 0.16 expand to `impl` blocks inside functions
+// and check for an `addr_of` cfg that newer compilers do not know about
+#![allow(non_local_definitions, unexpected_cfgs)]
+
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use std::str::FromStr;
 
 use pyo3::basic::CompareOp;
-use pyo3::exceptions::{PyOSError, PyValueError};
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyOSError, PyValueError};
 use pyo3::prelude::*;
 use pyo3::types::{PyDateTime, PyModule, PyTuple, PyType};
+use time::OffsetDateTime;
+
+create_exception!(
+    denote,
+    DenoteError,
+    PyException,
+    "Base class for denote errors"
+);
+create_exception!(
+    denote,
+    DenoteParseError,
+    DenoteError,
+    "Invalid id, file name or front matter"
+);
+create_exception!(
+    denote,
+    DenoteIOError,
+    PyOSError,
+    "Reading or writing notes failed"
+);
 
 fn unwrap<T>(result: crate::Result<T>) -> PyResult<T> {
     match result {
@@ -15,8 +41,10 @@ fn unwrap<T>(result: crate::Result<T>) -> PyResult<T> {
 
 fn to_python_result<T>(error: crate::Error) -> PyResult<T> {
     match error {
-        crate::Error::ParseError(e) => Err(PyValueError::new_err(e)),
-        crate::Error::OSError(e) => Err(PyOSError::new_err(e)),
+        crate::Error::ParseError(e) => Err(DenoteParseError::new_err(e)),
+        crate::Error::OSError(e) => Err(DenoteIOError::new_err(e)),
+        e @ crate::Error::Yaml { .. } => Err(DenoteParseError::new_err(e.to_string())),
+        e @ crate::Error::Io { .. } => Err(DenoteIOError::new_err(e.to_string())),
     }
 }
 
@@ -30,6 +58,26 @@ fn path_buf_to_pathlib(path_buf: PathBuf) -> PyResult<PyObject> {
     })
 }
 
+fn offset_date_time_to_datetime(date: OffsetDateTime) -> PyResult<PyObject> {
+    Python::with_gil(|py| {
+        let datetime = PyModule::import(py, "datetime")?;
+        let utc: PyObject = datetime.getattr("timezone")?.getattr("utc")?.into();
+        let res = PyDateTime::new(
+            py,
+            date.year(),
+            u8::from(date.month()),
+            date.day(),
+            date.hour(),
+            date.minute(),
+            date.second(),
+            0,
+            Some(&utc),
+        )?;
+        let res: &PyAny = res.as_ref();
+        Ok(res.into())
+    })
+}
+
 #[pyfunction]
 fn slugify(title: &str) -> PyResult<String> {
     Ok(slug::slugify(title))
@@ -78,6 +126,16 @@ impl Id {
         Ok(Self { _inner: id })
     }
 
+    #[classmethod]
+    fn from_unix_secs(_cls: &PyType, secs: i64) -> PyResult<Self> {
+        let id = unwrap(crate::Id::from_unix_secs(secs))?;
+        Ok(Self { _inner: id })
+    }
+
+    fn to_unix_secs(&self) -> PyResult<i64> {
+        unwrap(self._inner.to_unix_secs())
+    }
+
     fn __richcmp__(&self, other: &Self, op: CompareOp) -> PyResult<bool> {
         Ok(match op {
             CompareOp::Eq => self._inner == other._inner,
@@ -90,8 +148,7 @@ impl Id {
     }
 
     fn __str__(slf: PyRef<'_, Self>) -> String {
-        let res = slf._inner.as_str();
-        res.to_string()
+        slf._inner.to_string()
     }
 
     fn __repr__(slf: PyRef<'_, Self>) -> String {
@@ -107,10 +164,25 @@ struct Metadata {
 
 #[pymethods]
 impl Metadata {
+    /// `normalize_keywords` is one of "lowercase" (the default), "sort" or "keep"
     #[new]
-    fn new(id: &Id, title: String, keywords: Vec<String>, extension: String) -> PyResult<Self> {
+    #[args(normalize_keywords = "\"lowercase\"")]
+    fn new(
+        id: &Id,
+        title: Option<String>,
+        keywords: Vec<String>,
+        extension: String,
+        normalize_keywords: &str,
+    ) -> PyResult<Self> {
         let id = &id._inner;
-        let metadata = crate::Metadata::new(id.clone(), title, keywords, extension);
+        let normalization = unwrap(crate::KeywordNormalization::from_str(normalize_keywords))?;
+        let metadata = crate::Metadata::new_with_normalization(
+            id.clone(),
+            title,
+            keywords,
+            extension,
+            normalization,
+        );
         Ok(Self { _inner: metadata })
     }
 
@@ -125,7 +197,13 @@ impl Metadata {
     }
 
     #[getter]
-    fn title(&self) -> &str {
+    fn filename_slug(&self) -> &str {
+        self._inner.filename_slug()
+    }
+
+    /// None when the note has no title
+    #[getter]
+    fn title(&self) -> Option<&str> {
         self._inner.title()
     }
 
@@ -144,6 +222,19 @@ impl Metadata {
         self._inner.relative_path().to_string_lossy().to_string()
     }
 
+    #[getter]
+    fn created_at(&self) -> PyResult<PyObject> {
+        offset_date_time_to_datetime(unwrap(self._inner.created_at())?)
+    }
+
+    fn add_keyword(&mut self, keyword: &str) {
+        self._inner.add_keyword(keyword)
+    }
+
+    fn remove_keyword(&mut self, keyword: &str) {
+        self._inner.remove_keyword(keyword)
+    }
+
     fn __richcmp__(&self, other: &Metadata, op: CompareOp) -> PyResult<bool> {
         Ok(match op {
             CompareOp::Eq => self._inner == other._inner,
@@ -156,8 +247,7 @@ impl Metadata {
     }
 
     fn __str__(slf: PyRef<'_, Self>) -> String {
-        let metadata = &slf._inner;
-        format!("{metadata:?}")
+        slf._inner.to_string()
     }
 
     fn __repr__(slf: PyRef<'_, Self>) -> String {
@@ -173,8 +263,15 @@ struct FrontMatter {
 
 #[pymethods]
 impl FrontMatter {
+    #[new]
+    fn new(title: Option<String>, date: String, keywords: Vec<String>) -> Self {
+        let inner = crate::FrontMatter::new(title, date, keywords);
+        Self { _inner: inner }
+    }
+
+    /// None when the note has no title
     #[getter]
-    fn title(&self) -> &str {
+    fn title(&self) -> Option<&str> {
         self._inner.title()
     }
 
@@ -217,7 +314,11 @@ impl FrontMatter {
 
 #[pyfunction]
 fn get_note_from_markdown(id: &Id, contents: String) -> PyResult<Note> {
-    let inner = unwrap(crate::get_note_from_markdown(id._inner.clone(), contents))?;
+    let inner = unwrap(crate::get_note_from_markdown(
+        id._inner.clone(),
+        contents,
+        Default::default(),
+    ))?;
     Ok(Note { _inner: inner })
 }
 
@@ -230,10 +331,7 @@ struct Note {
 impl Note {
     #[new]
     fn new(metadata: &Metadata, text: &str) -> Self {
-        let inner_note = crate::Note {
-            metadata: metadata._inner.clone(),
-            text: text.to_string(),
-        };
+        let inner_note = crate::Note::new(metadata._inner.clone(), text.to_string());
         Note { _inner: inner_note }
     }
 
@@ -261,6 +359,34 @@ impl Note {
         self._inner.id()
     }
 
+    #[getter]
+    fn created_at(&self) -> PyResult<PyObject> {
+        offset_date_time_to_datetime(unwrap(self._inner.created_at())?)
+    }
+
+    #[getter]
+    fn body_text(&self) -> &str {
+        self._inner.body_text()
+    }
+
+    fn as_markdown(&self) -> String {
+        self._inner.as_markdown()
+    }
+
+    /// Code fences are counted as words too
+    fn word_count(&self) -> usize {
+        self._inner.word_count()
+    }
+
+    fn reading_time_minutes(&self, wpm: u32) -> u32 {
+        self._inner.reading_time_minutes(wpm)
+    }
+
+    #[getter]
+    fn content_hash(&self) -> String {
+        self._inner.content_hash()
+    }
+
     pub fn dump(&self) -> String {
         self._inner.dump()
     }
@@ -277,9 +403,61 @@ impl Note {
     }
 
     fn __str__(slf: PyRef<'_, Self>) -> String {
+        slf._inner.to_string()
+    }
+
+    fn __repr__(slf: PyRef<'_, Self>) -> String {
         let inner = &slf._inner;
         format!("{inner:?}")
     }
+}
+
+#[pyclass]
+struct RepoStats {
+    _inner: crate::RepoStats,
+}
+
+#[pymethods]
+impl RepoStats {
+    #[getter]
+    fn total_notes(&self) -> usize {
+        self._inner.total_notes
+    }
+
+    #[getter]
+    fn total_words(&self) -> usize {
+        self._inner.total_words
+    }
+
+    #[getter]
+    fn total_bytes(&self) -> u64 {
+        self._inner.total_bytes
+    }
+
+    #[getter]
+    fn years(&self) -> Vec<u16> {
+        self._inner.years.clone()
+    }
+
+    #[getter]
+    fn notes_per_year(&self) -> BTreeMap<u16, usize> {
+        self._inner.notes_per_year.clone()
+    }
+
+    #[getter]
+    fn top_keywords(&self) -> Vec<(String, usize)> {
+        self._inner.top_keywords.clone()
+    }
+
+    #[getter]
+    fn unique_keywords(&self) -> usize {
+        self._inner.unique_keywords
+    }
+
+    #[getter]
+    fn most_common_keyword(&self) -> Option<String> {
+        self._inner.most_common_keyword.clone()
+    }
 
     fn __repr__(slf: PyRef<'_, Self>) -> String {
         let inner = &slf._inner;
@@ -287,6 +465,37 @@ impl Note {
     }
 }
 
+/// Load the notes of a repository one at a time.
+/// Notes that cannot be loaded are skipped with a warning
+#[pyclass]
+struct NoteIterator {
+    repository: Py<NotesRepository>,
+    relative_paths: std::vec::IntoIter<PathBuf>,
+}
+
+#[pymethods]
+impl NoteIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<'_, Self>, py: Python<'_>) -> PyResult<Option<Note>> {
+        while let Some(relative_path) = slf.relative_paths.next() {
+            let repository = slf.repository.borrow(py);
+            match repository._inner.load(&relative_path) {
+                Ok(note) => return Ok(Some(Note { _inner: note })),
+                Err(e) => {
+                    let message = format!("Skipping {relative_path:?}: {e}");
+                    // Note: UserWarning is not exposed by pyo3, look it up instead
+                    let user_warning = py.import("builtins")?.getattr("UserWarning")?;
+                    PyErr::warn(py, user_warning, &message, 1)?;
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
 #[pyclass]
 struct NotesRepository {
     _inner: crate::NotesRepository,
@@ -310,6 +519,19 @@ impl NotesRepository {
         Ok(NotesRepository { _inner: inner })
     }
 
+    #[classmethod]
+    fn open_or_create(_cls: &PyType, base_path: &PyAny) -> PyResult<Self> {
+        let as_str = base_path.to_string();
+        let path = match PathBuf::from_str(&as_str) {
+            Ok(p) => p,
+            Err(e) => {
+                return Err(PyValueError::new_err(e.to_string()));
+            }
+        };
+        let inner = unwrap(crate::NotesRepository::open_or_create(&path))?;
+        Ok(NotesRepository { _inner: inner })
+    }
+
     #[getter]
     fn base_path(&self) -> String {
         self._inner.base_path().to_string_lossy().to_string()
@@ -333,25 +555,95 @@ impl NotesRepository {
         Ok(Note { _inner: note })
     }
 
+    fn load_metadata(&self, relative_path: &PyAny) -> PyResult<Metadata> {
+        let as_path = PathBuf::from_str(&relative_path.to_string())?;
+        let metadata = unwrap(self._inner.load_metadata(&as_path))?;
+        Ok(Metadata { _inner: metadata })
+    }
+
+    /// Return the path of the note relative to the base path, as expected by `load`
     fn save(&self, note: &Note) -> PyResult<PyObject> {
         let path = unwrap(self._inner.save(&note._inner))?;
         path_buf_to_pathlib(path)
     }
 
+    fn save_full(&self, note: &Note) -> PyResult<PyObject> {
+        let path = unwrap(self._inner.save_full(&note._inner))?;
+        path_buf_to_pathlib(path)
+    }
+
+    fn count(&self) -> PyResult<usize> {
+        unwrap(self._inner.count())
+    }
+
+    fn stats(&self) -> PyResult<RepoStats> {
+        let stats = unwrap(self._inner.stats())?;
+        Ok(RepoStats { _inner: stats })
+    }
+
+    #[args(fast = "false")]
+    fn statistics(&self, fast: bool) -> PyResult<RepoStats> {
+        let stats = unwrap(self._inner.statistics(fast))?;
+        Ok(RepoStats { _inner: stats })
+    }
+
+    fn notes_by_keyword(&self) -> PyResult<BTreeMap<String, Vec<String>>> {
+        let by_keyword = unwrap(self._inner.notes_by_keyword_sorted())?;
+        Ok(by_keyword
+            .into_iter()
+            .map(|(keyword, paths)| {
+                let paths = paths
+                    .iter()
+                    .map(|p| p.to_string_lossy().to_string())
+                    .collect();
+                (keyword, paths)
+            })
+            .collect())
+    }
+
+    fn __len__(&self) -> PyResult<usize> {
+        unwrap(self._inner.count())
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyResult<NoteIterator> {
+        // Files whose name does not match the denote naming convention
+        // are never listed
+        let relative_paths = unwrap(slf._inner.note_paths())?;
+        Ok(NoteIterator {
+            repository: slf.into(),
+            relative_paths: relative_paths.into_iter(),
+        })
+    }
+
     fn __str__(slf: PyRef<'_, Self>) -> String {
         let inner = &slf._inner;
         format!("{inner:?}")
     }
 }
 
+/// Python bindings for denote
+///
+/// >>> import tempfile
+/// >>> from denote import Id, Metadata, Note, NotesRepository
+/// >>> notes = NotesRepository.open(tempfile.mkdtemp())
+/// >>> metadata = Metadata(Id("20220707T142708"), "A title", ["k1"], "md")
+/// >>> _ = notes.save(Note(text="my note", metadata=metadata))
+/// >>> len(notes)
+/// 1
+/// >>> [note.metadata.title for note in notes]
+/// ['A title']
 #[pymodule]
-fn denote(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
+fn denote(py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    m.add("DenoteError", py.get_type::<DenoteError>())?;
+    m.add("DenoteParseError", py.get_type::<DenoteParseError>())?;
+    m.add("DenoteIOError", py.get_type::<DenoteIOError>())?;
     m.add_function(wrap_pyfunction!(slugify, m)?)?;
     m.add_function(wrap_pyfunction!(get_note_from_markdown, m)?)?;
     m.add_class::<Id>()?;
     m.add_class::<Metadata>()?;
     m.add_class::<FrontMatter>()?;
     m.add_class::<Note>()?;
+    m.add_class::<RepoStats>()?;
     m.add_class::<NotesRepository>()?;
     Ok(())
 }